@@ -1,3 +1,4 @@
+use diagnostics::Report;
 use logos::{Lexer, Logos, Slice, Source};
 
 #[derive(Logos, Debug, PartialEq, Copy, Clone)]
@@ -106,6 +107,27 @@ where
     lex.advance();
 }
 
+/// Reports for the parts of `source` the lexer cannot read.
+pub fn errors(source: &str) -> Vec<Report> {
+    let mut lex = Token::lexer(source);
+    let mut reports = Vec::new();
+    while lex.token != Token::End {
+        if lex.token == Token::UnexpectedToken {
+            let report = if lex.slice().starts_with("/*") {
+                Report::error("unterminated comment")
+                    .label(lex.range(), "")
+                    .help("close the comment with `*/`")
+            } else {
+                Report::error(format!("unexpected {:?}", lex.slice())).label(lex.range(), "")
+            };
+            reports.push(report);
+        }
+        lex.advance();
+    }
+
+    reports
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -145,6 +167,15 @@ mod test {
         assert_lex("<!-- -->", &[(Token::CDO, "<!--"), (Token::CDC, "-->")]);
     }
 
+    #[test]
+    fn errors() {
+        assert!(super::errors("a { color: red; }").is_empty());
+        let reports = super::errors("  /* hello world  ");
+        assert_eq!(reports.len(), 1);
+        assert_eq!(reports[0].message, "unterminated comment");
+        assert_eq!(reports[0].labels[0].range, 2..18);
+    }
+
     #[test]
     fn strings() {
         assert_lex(
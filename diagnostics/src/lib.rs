@@ -0,0 +1,352 @@
+//! Rendering problems in the input the way rustc does: a message, the line
+//! of the source it is about with the span underlined, and hints on how to
+//! fix it.
+//!
+//! ```text
+//! error: unexpected character 'x'
+//!  --> data.json:2:8
+//!   |
+//! 2 |   "a": x,
+//!   |        ^ not a valid value
+//!   |
+//!   = help: strings need to be quoted
+//! ```
+//!
+//! Spans are byte ranges in the source, so every crate can report its own
+//! errors without agreeing on a position type.
+
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+use std::ops::Range;
+
+/// How serious a report is.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        })
+    }
+}
+
+/// A span of the source with a note on it.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Label {
+    /// The bytes of the source it is about, which may be empty to point at
+    /// the place between two characters.
+    pub range: Range<usize>,
+    pub message: String,
+    /// Whether this is where the problem is, rather than related context.
+    pub primary: bool,
+}
+
+/// A problem in the source, ready to be rendered.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Report {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub help: Vec<String>,
+}
+
+impl Report {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Report {
+            severity,
+            message: message.into(),
+            labels: Vec::new(),
+            help: Vec::new(),
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Report::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Report::new(Severity::Warning, message)
+    }
+
+    /// Marks where the problem is, the message can be empty.
+    pub fn label(mut self, range: Range<usize>, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            range,
+            message: message.into(),
+            primary: true,
+        });
+        self
+    }
+
+    /// Marks a related part of the source, like the first of two duplicates.
+    pub fn secondary_label(mut self, range: Range<usize>, message: impl Into<String>) -> Self {
+        self.labels.push(Label {
+            range,
+            message: message.into(),
+            primary: false,
+        });
+        self
+    }
+
+    /// Adds a hint on how to fix the problem.
+    pub fn help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const RED: &str = "\x1b[1;31m";
+const YELLOW: &str = "\x1b[1;33m";
+const BLUE: &str = "\x1b[1;34m";
+const CYAN: &str = "\x1b[1;36m";
+
+/// How many columns a tab is shown as.
+const TAB_WIDTH: usize = 4;
+
+/// Renders reports as text, with or without ANSI colors.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct Renderer {
+    color: bool,
+}
+
+impl Renderer {
+    /// A renderer without colors.
+    pub fn new() -> Self {
+        Renderer::default()
+    }
+
+    /// A renderer for writing to stderr, with colors if it is a terminal
+    /// and `NO_COLOR` is not set.
+    pub fn for_stderr() -> Self {
+        let no_color = env::var_os("NO_COLOR").is_some_and(|value| !value.is_empty());
+        Renderer::new().color(io::stderr().is_terminal() && !no_color)
+    }
+
+    pub fn color(mut self, color: bool) -> Self {
+        self.color = color;
+        self
+    }
+
+    /// Renders a report about `source`, which is called `name` in the
+    /// output, like a file name.
+    pub fn render(&self, name: &str, source: &str, report: &Report) -> String {
+        let severity = match report.severity {
+            Severity::Error => RED,
+            Severity::Warning => YELLOW,
+        };
+        let mut output = format!(
+            "{}{}\n",
+            self.paint(severity, &format!("{}:", report.severity)),
+            self.paint(BOLD, &format!(" {}", report.message))
+        );
+
+        let mut labels: Vec<_> = report.labels.iter().collect();
+        labels.sort_by_key(|label| label.range.start);
+        let lines: Vec<_> = labels
+            .iter()
+            .map(|label| Line::locate(source, label.range.start))
+            .collect();
+        let width = lines
+            .iter()
+            .map(|line| line.number.to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = self.paint(BLUE, &format!("{:width$} |", "", width = width));
+
+        let primary = labels.iter().zip(&lines).find(|(label, _)| label.primary);
+        if let Some((label, line)) = primary.or_else(|| labels.iter().zip(&lines).next()) {
+            let column = line.column(source, label.range.start);
+            output.push_str(&format!(
+                "{:width$}{} {}:{}:{}\n{}\n",
+                "",
+                self.paint(BLUE, "-->"),
+                name,
+                line.number,
+                column,
+                gutter,
+                width = width
+            ));
+        }
+        let mut previous = None;
+        for (label, line) in labels.iter().zip(&lines) {
+            // Labels on the same line share it
+            if previous != Some(line.number) {
+                let text = &source[line.start..line.end];
+                let number = format!("{:width$} |", line.number, width = width);
+                output.push_str(&format!("{} {}\n", self.paint(BLUE, &number), expand(text)));
+                previous = Some(line.number);
+            }
+
+            // Spans from other crates may not fall on character boundaries
+            let start = floor_char_boundary(source, label.range.start.min(line.end));
+            let end = ceil_char_boundary(source, label.range.end.clamp(start, line.end));
+            let indent = display_width(&source[line.start..start]);
+            let length = display_width(&source[start..end]).max(1);
+            let (style, mark) = if label.primary {
+                (severity, "^")
+            } else {
+                (BLUE, "-")
+            };
+            let underline = format!("{} {}", mark.repeat(length), label.message);
+            output.push_str(&format!(
+                "{} {}{}\n",
+                gutter,
+                " ".repeat(indent),
+                self.paint(style, underline.trim_end())
+            ));
+        }
+        if !report.help.is_empty() && !labels.is_empty() {
+            output.push_str(&format!("{}\n", gutter));
+        }
+        for help in &report.help {
+            output.push_str(&format!(
+                "{:width$} {} {}\n",
+                "",
+                self.paint(BLUE, "="),
+                self.paint(CYAN, "help:") + " " + help,
+                width = width
+            ));
+        }
+
+        output
+    }
+
+    fn paint(&self, style: &str, text: &str) -> String {
+        if self.color {
+            format!("{}{}{}", style, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// The line of the source a span starts on.
+struct Line {
+    /// The line number, starting at 1.
+    number: usize,
+    /// Where the line starts and ends in the source, without the line break.
+    start: usize,
+    end: usize,
+}
+
+impl Line {
+    fn locate(source: &str, offset: usize) -> Self {
+        let offset = floor_char_boundary(source, offset.min(source.len()));
+        let start = source[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let end = source[offset..]
+            .find('\n')
+            .map_or(source.len(), |i| offset + i);
+        let end = if source[start..end].ends_with('\r') {
+            end - 1
+        } else {
+            end
+        };
+        Line {
+            number: source[..start].matches('\n').count() + 1,
+            start,
+            end,
+        }
+    }
+
+    /// The column of `offset` on this line in characters, starting at 1.
+    fn column(&self, source: &str, offset: usize) -> usize {
+        let offset = floor_char_boundary(source, offset.clamp(self.start, self.end));
+        source[self.start..offset].chars().count() + 1
+    }
+}
+
+fn floor_char_boundary(source: &str, mut offset: usize) -> usize {
+    while !source.is_char_boundary(offset) {
+        offset -= 1;
+    }
+    offset
+}
+
+fn ceil_char_boundary(source: &str, mut offset: usize) -> usize {
+    while !source.is_char_boundary(offset) {
+        offset += 1;
+    }
+    offset
+}
+
+/// The line as shown, with tabs as spaces so the underline lines up.
+fn expand(text: &str) -> String {
+    text.replace('\t', &" ".repeat(TAB_WIDTH))
+}
+
+fn display_width(text: &str) -> usize {
+    text.chars()
+        .map(|c| if c == '\t' { TAB_WIDTH } else { 1 })
+        .sum()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn render() {
+        let source = "{\n  \"a\": x,\n\t\"b\": [1, 2}\n";
+        let report = Report::error("unexpected character 'x'")
+            .label(9..10, "not a valid value")
+            .help("strings need to be quoted");
+        assert_eq!(
+            Renderer::new().render("data.json", source, &report),
+            "error: unexpected character 'x'
+ --> data.json:2:8
+  |
+2 |   \"a\": x,
+  |        ^ not a valid value
+  |
+  = help: strings need to be quoted
+"
+        );
+
+        let report = Report::warning("mismatched '}'")
+            .label(23..24, "")
+            .secondary_label(18..19, "opened here");
+        assert_eq!(
+            Renderer::new().render("data.json", source, &report),
+            "warning: mismatched '}'
+ --> data.json:3:12
+  |
+3 |     \"b\": [1, 2}
+  |          - opened here
+  |               ^
+"
+        );
+
+        // Nothing to underline at the end of the input
+        let report = Report::error("unexpected end of input").label(4..4, "");
+        assert_eq!(
+            Renderer::new().render("-", "[1,\n", &report),
+            "error: unexpected end of input\n --> -:2:1\n  |\n2 | \n  | ^\n"
+        );
+
+        // Spans inside a character cover all of it
+        let report = Report::error("invalid escape").label(3..5, "");
+        assert_eq!(
+            Renderer::new().render("-", "[\"é😀\"]", &report),
+            "error: invalid escape\n --> -:1:3\n  |\n1 | [\"é😀\"]\n  |   ^^\n"
+        );
+    }
+
+    #[test]
+    fn color() {
+        let report = Report::error("invalid number").label(0..2, "");
+        let rendered = Renderer::new().color(true).render("a.json", "1.", &report);
+        assert!(rendered.starts_with("\x1b[1;31merror:\x1b[0m\x1b[1m invalid number"));
+        assert!(rendered.contains("\x1b[1;31m^^\x1b[0m"));
+        assert!(!Renderer::new()
+            .render("a.json", "1.", &report)
+            .contains('\x1b'));
+    }
+}
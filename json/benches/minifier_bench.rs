@@ -1,26 +1,328 @@
 use criterion::*;
 use json::minify::Minify;
+use json::{Lexer, Token};
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::fmt::{self, Write};
 use std::fs::File;
-use std::io::BufReader;
-use std::io::Read;
+use std::io::{self, BufReader, Read};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
-fn criterion_benchmark(c: &mut Criterion) {
-    let file = File::open("./tests/fixtures/large.json").unwrap();
+/// Counts allocations, to show how much memory writing the output takes on
+/// top of the output itself.
+struct Counting;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+static ALLOCATED: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(layout.size(), Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        ALLOCATED.fetch_add(new_size, Ordering::Relaxed);
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+/// How many allocations `f` makes, and how many bytes they add up to.
+fn allocations<T>(f: impl FnOnce() -> T) -> (usize, usize) {
+    let (count, bytes) = (
+        ALLOCATIONS.load(Ordering::Relaxed),
+        ALLOCATED.load(Ordering::Relaxed),
+    );
+    drop(f());
+    (
+        ALLOCATIONS.load(Ordering::Relaxed) - count,
+        ALLOCATED.load(Ordering::Relaxed) - bytes,
+    )
+}
+
+fn read_file(filename: &str) -> String {
+    let file = File::open(filename).unwrap();
     let mut buf_reader = BufReader::new(file);
+    let mut contents = String::new();
+    buf_reader.read_to_string(&mut contents).unwrap();
+    contents
+}
+
+/// A pretty-printed array of objects with long string values, where most of
+/// the time goes to scanning string bodies and whitespace.
+fn strings() -> String {
+    let text = "Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do \
+                eiusmod tempor incididunt ut labore et dolore magna aliqua. ";
+    let mut json = String::from("[\n");
+    for i in 0..2000 {
+        json.push_str(&format!(
+            "    {{\n        \"id\": {},\n        \"text\": \"{}{}\"\n    }},\n",
+            i,
+            text.repeat(4),
+            i
+        ));
+    }
+    json.push_str("    null\n]\n");
+    json
+}
+
+/// The char-based JSON lexer the byte-based one replaced, without comments
+/// and JSON5, kept to compare lexing throughput against.
+mod chars {
+    use json::{Error, Position, Token};
+    use std::iter::Peekable;
+    use std::str::Chars;
+
+    pub struct Lexer<'a> {
+        source: &'a str,
+        input: Peekable<Chars<'a>>,
+        position: Position,
+    }
+
+    impl<'a> Lexer<'a> {
+        pub fn new(input: &'a str) -> Self {
+            Lexer {
+                source: input,
+                input: input.chars().peekable(),
+                position: Position::default(),
+            }
+        }
+
+        fn read(&mut self) -> Option<char> {
+            let next = self.input.next();
+            if let Some(c) = next {
+                self.position.offset += c.len_utf8();
+                if c == '\n' {
+                    self.position.line += 1;
+                    self.position.column = 1;
+                } else {
+                    self.position.column += 1;
+                }
+            }
+            next
+        }
+
+        fn slice(&self, start: Position) -> &'a str {
+            &self.source[start.offset..self.position.offset]
+        }
+
+        fn read_literal(&mut self, rest: &str, token: Token<'a>) -> Result<Token<'a>, Error> {
+            for expected in rest.chars() {
+                let position = self.position;
+                match self.read() {
+                    Some(c) if c == expected => continue,
+                    Some(c) => return Err(Error::UnexpectedCharacter(c, position)),
+                    None => return Err(Error::UnexpectedEof(position)),
+                }
+            }
+
+            Ok(token)
+        }
+
+        fn read_string(&mut self, start: Position) -> Result<&'a str, Error> {
+            loop {
+                let position = self.position;
+                match self.read() {
+                    Some('"') => {
+                        let string = self.slice(start);
+                        return Ok(&string[1..string.len() - 1]);
+                    }
+                    Some('\\') => self.read_escape(start, position)?,
+                    Some(c) if c < '\u{20}' => return Err(Error::ControlCharacter(position)),
+                    Some(_) => {}
+                    None => return Err(Error::UnterminatedString(start)),
+                }
+            }
+        }
+
+        fn read_escape(&mut self, start: Position, escape: Position) -> Result<(), Error> {
+            match self.read() {
+                Some(c) if "\"\\/bfnrt".contains(c) => {}
+                Some('u') => match self.read_hex(start, escape)? {
+                    0xD800..=0xDBFF => {
+                        let low = self.position;
+                        match (self.read(), self.read()) {
+                            (Some('\\'), Some('u')) => {}
+                            (None, _) | (Some('\\'), None) => {
+                                return Err(Error::UnterminatedString(start))
+                            }
+                            _ => return Err(Error::UnpairedSurrogate(escape)),
+                        }
+                        if !matches!(self.read_hex(start, low)?, 0xDC00..=0xDFFF) {
+                            return Err(Error::UnpairedSurrogate(escape));
+                        }
+                    }
+                    0xDC00..=0xDFFF => return Err(Error::UnpairedSurrogate(escape)),
+                    _ => {}
+                },
+                Some(_) => return Err(Error::InvalidEscape(escape)),
+                None => return Err(Error::UnterminatedString(start)),
+            }
+
+            Ok(())
+        }
+
+        fn read_hex(&mut self, start: Position, escape: Position) -> Result<u32, Error> {
+            let mut unit = 0;
+            for _ in 0..4 {
+                let c = self.read().ok_or(Error::UnterminatedString(start))?;
+                let digit = c.to_digit(16).ok_or(Error::InvalidEscape(escape))?;
+                unit = unit * 16 + digit;
+            }
+
+            Ok(unit)
+        }
+
+        fn read_number(&mut self, start: Position) -> Result<Token<'a>, Error> {
+            while let Some(&c) = self.input.peek() {
+                if c == ',' || c == ']' || c == '}' || c.is_whitespace() {
+                    break;
+                }
+                if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+                    return Err(Error::InvalidNumber(start));
+                }
+                self.read();
+            }
+
+            Ok(Token::Number(self.slice(start).into()))
+        }
+
+        pub fn next_token(&mut self) -> Result<Token<'a>, Error> {
+            while self.input.peek().is_some_and(|c| c.is_whitespace()) {
+                self.read();
+            }
+
+            let start = self.position;
+            let token = match self.read() {
+                Some(':') => Token::Colon,
+                Some(',') => Token::Comma,
+                Some('{') => Token::ObjectStart,
+                Some('}') => Token::ObjectEnd,
+                Some('[') => Token::ArrayStart,
+                Some(']') => Token::ArrayEnd,
+                Some('n') => self.read_literal("ull", Token::Null)?,
+                Some('t') => self.read_literal("rue", Token::True)?,
+                Some('f') => self.read_literal("alse", Token::False)?,
+                Some('"') => Token::String(self.read_string(start)?.into()),
+                Some(c) if c.is_ascii_digit() || c == '-' => self.read_number(start)?,
+                Some(c) => return Err(Error::UnexpectedCharacter(c, start)),
+                None => Token::EndOfFile,
+            };
+
+            Ok(token)
+        }
+    }
+}
+
+/// How many tokens the char-based lexer finds in `input`.
+fn lex_chars(input: &str) -> usize {
+    let mut lexer = chars::Lexer::new(input);
+    let mut count = 0;
+    while lexer.next_token().unwrap() != Token::EndOfFile {
+        count += 1;
+    }
+    count
+}
+
+/// How many tokens the lexer finds in `input`.
+fn lex_bytes(input: &str) -> usize {
+    Lexer::new(input).map(Result::unwrap).count()
+}
+
+/// Collects the pieces `Display` writes the output in.
+#[derive(Default)]
+struct Fragments(Vec<String>);
+
+impl Write for Fragments {
+    fn write_str(&mut self, fragment: &str) -> fmt::Result {
+        self.0.push(fragment.to_string());
+        Ok(())
+    }
+}
+
+/// How `Display` rendered the output before `write_to`: every fragment was
+/// pushed to a `Vec<&str>`, joined, and the result written to the formatter.
+fn joined(fragments: &[&str]) -> String {
+    let mut output = Vec::new();
+    for fragment in fragments {
+        output.push(*fragment);
+    }
     let mut string = String::new();
-    let bytes: Vec<_> = string.bytes().collect();
-    buf_reader.read_to_string(&mut string).unwrap();
+    write!(string, "{}", output.join("")).unwrap();
+    string
+}
+
+fn minify(c: &mut Criterion, name: &str, input: String) {
+    let bytes = input.len() as u32;
+    assert_eq!(lex_chars(&input), lex_bytes(&input));
+    let chars = input.clone();
+    let lexed = input.clone();
+    let copy = input.clone();
+    let minified = Minify::new(&input).unwrap();
+    let output = minified.to_string().len();
+    let mut fragments = Fragments::default();
+    write!(fragments, "{}", minified).unwrap();
+    let fragments: Vec<&str> = fragments.0.iter().map(String::as_str).collect();
+    let (count, allocated) = allocations(|| joined(&fragments));
+    println!(
+        "minify/{}: joining {} bytes of output into a String makes {} allocations of {} bytes",
+        name, output, count, allocated
+    );
+    let (count, allocated) = allocations(|| format!("{}", minified));
+    println!(
+        "minify/{}: formatting {} bytes of output into a String makes {} allocations of {} bytes",
+        name, output, count, allocated
+    );
+    let (count, allocated) = allocations(|| minified.write_to(io::sink()));
+    println!(
+        "minify/{}: writing {} bytes of output with write_to makes {} allocations of {} bytes",
+        name, output, count, allocated
+    );
 
     c.bench(
         "minify",
-        Benchmark::new(
-            "wat",
-            move |b| b.iter_with_setup(
-                || string.as_str(),
-                |contents| format!("{}", Minify::new(contents)),
-            ),
-        ).throughput(Throughput::Bytes(bytes.len() as u32)),
+        Benchmark::new(name, move |b| {
+            b.iter(|| format!("{}", Minify::new(&copy).unwrap()))
+        })
+        .throughput(Throughput::Bytes(bytes)),
+    );
+
+    c.bench(
+        "lex",
+        Benchmark::new(format!("{}/chars", name), move |b| {
+            b.iter(|| lex_chars(&chars))
+        })
+        .with_function(format!("{}/bytes", name), move |b| {
+            b.iter(|| lex_bytes(&lexed))
+        })
+        .throughput(Throughput::Bytes(bytes)),
     );
+
+    c.bench(
+        "write",
+        Benchmark::new(name, move |b| {
+            let minified = Minify::new(&input).unwrap();
+            b.iter(|| minified.write_to(io::sink()).unwrap())
+        })
+        .throughput(Throughput::Bytes(bytes)),
+    );
+}
+
+// Compare against another revision by running `cargo bench -- --save-baseline
+// before` there and `cargo bench -- --baseline before` here. The `lex` group
+// compares the lexer with the char-based one it replaced on the same input,
+// run it alone with `cargo bench --bench minifier_bench -- lex/`.
+fn criterion_benchmark(c: &mut Criterion) {
+    minify(c, "large", read_file("./tests/fixtures/large.json"));
+    minify(c, "strings", strings());
 }
 
 criterion_group!(benches, criterion_benchmark);
@@ -0,0 +1,244 @@
+//! Canonical JSON as specified by RFC 8785, the JSON Canonicalization
+//! Scheme: no whitespace, object members sorted by the UTF-16 code units of
+//! their keys, numbers written the way ECMAScript writes them and strings
+//! with as few escapes as possible.
+//!
+//! Sorting means the whole document has to be read first, so unlike the
+//! rest of the pipeline this builds a tree of the document and only writes
+//! it once the input has ended.
+
+use crate::error::{Error, Position};
+use crate::escape::{escape, unescape};
+use crate::tokens::Token;
+
+enum Value {
+    /// A string, number or literal, already in canonical form.
+    Scalar(Token<'static>),
+    Array(Vec<Value>),
+    /// Members with their decoded keys, sorted.
+    Object(Vec<(String, Value)>),
+}
+
+/// A decoded key and where it is in the input.
+type Key = (String, Position);
+
+enum Container {
+    Array(Vec<Value>),
+    /// The members so far and the key of the member being read.
+    Object(Vec<(Key, Value)>, Option<Key>),
+}
+
+/// Collects a document, which has to be valid JSON, one token at a time.
+#[derive(Default)]
+pub(crate) struct Canonical {
+    stack: Vec<Container>,
+    values: Vec<Value>,
+}
+
+impl Canonical {
+    /// Starts over at the beginning of a document.
+    pub fn reset(&mut self) {
+        self.stack.clear();
+        self.values.clear();
+    }
+
+    pub fn push(&mut self, token: Token, position: Position) -> Result<(), Error> {
+        let value = match token {
+            Token::ObjectStart => {
+                self.stack.push(Container::Object(Vec::new(), None));
+                return Ok(());
+            }
+            Token::ArrayStart => {
+                self.stack.push(Container::Array(Vec::new()));
+                return Ok(());
+            }
+            Token::ObjectEnd | Token::ArrayEnd => match self.stack.pop() {
+                Some(Container::Object(members, _)) => Value::Object(sort(members)?),
+                Some(Container::Array(elements)) => Value::Array(elements),
+                None => return Err(Error::UnexpectedToken(token.describe(), position)),
+            },
+            Token::String(string) => {
+                if let Some(Container::Object(_, key @ None)) = self.stack.last_mut() {
+                    *key = Some((unescape(&string).into_owned(), position));
+                    return Ok(());
+                }
+                let string = escape(&unescape(&string)).into_owned();
+                Value::Scalar(Token::String(string.into()))
+            }
+            Token::Number(number) => match number_to_canonical(&number) {
+                Some(number) => Value::Scalar(Token::Number(number.into())),
+                None => return Err(Error::NumberOutOfRange(position)),
+            },
+            Token::Null => Value::Scalar(Token::Null),
+            Token::True => Value::Scalar(Token::True),
+            Token::False => Value::Scalar(Token::False),
+            Token::Comma | Token::Colon | Token::EndOfFile => return Ok(()),
+            Token::SingleQuoted(_) | Token::Identifier(_) => {
+                return Err(Error::UnexpectedToken(token.describe(), position))
+            }
+        };
+
+        match self.stack.last_mut() {
+            Some(Container::Array(elements)) => elements.push(value),
+            Some(Container::Object(members, key)) => match key.take() {
+                Some(key) => members.push((key, value)),
+                None => return Err(Error::UnexpectedToken("value", position)),
+            },
+            None => self.values.push(value),
+        }
+
+        Ok(())
+    }
+
+    /// Passes the canonical form of every value that has been read to `emit`.
+    pub fn finish<'a, E, F>(&mut self, mut emit: F) -> Result<(), E>
+    where
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        for value in self.values.drain(..) {
+            write(value, &mut emit)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn write<'a, E, F>(value: Value, emit: &mut F) -> Result<(), E>
+where
+    F: FnMut(Token<'a>) -> Result<(), E>,
+{
+    match value {
+        Value::Scalar(token) => emit(token),
+        Value::Array(elements) => {
+            emit(Token::ArrayStart)?;
+            for (i, element) in elements.into_iter().enumerate() {
+                if i > 0 {
+                    emit(Token::Comma)?;
+                }
+                write(element, emit)?;
+            }
+            emit(Token::ArrayEnd)
+        }
+        Value::Object(members) => {
+            emit(Token::ObjectStart)?;
+            for (i, (key, value)) in members.into_iter().enumerate() {
+                if i > 0 {
+                    emit(Token::Comma)?;
+                }
+                emit(Token::String(escape(&key).into_owned().into()))?;
+                emit(Token::Colon)?;
+                write(value, emit)?;
+            }
+            emit(Token::ObjectEnd)
+        }
+    }
+}
+
+/// Sorts the members of an object by the UTF-16 code units of their keys.
+/// RFC 8785 only covers I-JSON, which has no duplicate keys, so they fail.
+fn sort(mut members: Vec<(Key, Value)>) -> Result<Vec<(String, Value)>, Error> {
+    // Stable, so of two members with the same key the duplicate comes second
+    members.sort_by(|((a, _), _), ((b, _), _)| a.encode_utf16().cmp(b.encode_utf16()));
+    if let Some(pair) = members.windows(2).find(|pair| pair[0].0 .0 == pair[1].0 .0) {
+        let (key, position) = &pair[1].0;
+        return Err(Error::DuplicateKey(key.clone(), *position));
+    }
+
+    Ok(members
+        .into_iter()
+        .map(|((key, _), value)| (key, value))
+        .collect())
+}
+
+/// Writes a JSON number the way ECMAScript's `Number.prototype.toString`
+/// does, or returns `None` if it is too large for a double.
+fn number_to_canonical(number: &str) -> Option<String> {
+    let value: f64 = number.parse().ok()?;
+    if !value.is_finite() {
+        return None;
+    }
+    if value == 0.0 {
+        return Some("0".to_string());
+    }
+
+    // Rust also finds the shortest digits that round trip, only the layout
+    // differs
+    let exponential = format!("{:e}", value.abs());
+    let (mantissa, exponent) = exponential.split_at(exponential.find('e').unwrap());
+    let digits = mantissa.replace('.', "");
+    let k = digits.len() as i32;
+    // The decimal point goes after the first `n` digits
+    let n = exponent[1..].parse::<i32>().unwrap() + 1;
+
+    let mut canonical = String::new();
+    if value < 0.0 {
+        canonical.push('-');
+    }
+    if k <= n && n <= 21 {
+        canonical.push_str(&digits);
+        canonical.extend((k..n).map(|_| '0'));
+    } else if 0 < n && n <= 21 {
+        canonical.push_str(&digits[..n as usize]);
+        canonical.push('.');
+        canonical.push_str(&digits[n as usize..]);
+    } else if -6 < n && n <= 0 {
+        canonical.push_str("0.");
+        canonical.extend((n..0).map(|_| '0'));
+        canonical.push_str(&digits);
+    } else {
+        canonical.push_str(&digits[..1]);
+        if k > 1 {
+            canonical.push('.');
+            canonical.push_str(&digits[1..]);
+        }
+        canonical.push('e');
+        canonical.push(if n > 0 { '+' } else { '-' });
+        canonical.push_str(&(n - 1).abs().to_string());
+    }
+
+    Some(canonical)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn numbers() {
+        // Including the examples from RFC 8785
+        let cases = [
+            ("0", "0"),
+            ("-0", "0"),
+            ("-0.0e10", "0"),
+            ("1", "1"),
+            ("1.0", "1"),
+            ("-1.50", "-1.5"),
+            ("1e2", "100"),
+            ("1E+30", "1e+30"),
+            ("4.50", "4.5"),
+            ("2e-3", "0.002"),
+            ("0.000001", "0.000001"),
+            ("0.0000001", "1e-7"),
+            ("1e21", "1e+21"),
+            ("1e20", "100000000000000000000"),
+            ("123456789012345678901", "123456789012345680000"),
+            ("333333333.33333329", "333333333.3333333"),
+            ("1.7976931348623157e308", "1.7976931348623157e+308"),
+            ("5e-324", "5e-324"),
+            ("-5e-324", "-5e-324"),
+            ("9007199254740993", "9007199254740992"),
+            ("295147905179352830000", "295147905179352830000"),
+            ("1e23", "1e+23"),
+        ];
+        for &(number, canonical) in &cases {
+            assert_eq!(
+                number_to_canonical(number).as_deref(),
+                Some(canonical),
+                "{}",
+                number
+            );
+        }
+        assert_eq!(number_to_canonical("1e400"), None);
+        assert_eq!(number_to_canonical("-1e309"), None);
+    }
+}
@@ -0,0 +1,42 @@
+//! Problems found in the input when minifying with error recovery, see
+//! `Minify::recover`.
+
+use crate::tokens::Span;
+use diagnostics::Report;
+use std::fmt;
+
+/// A problem in the input that was worked around.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    /// The part of the input with the problem, empty when something is
+    /// missing.
+    pub span: Span,
+    /// What is wrong, like "missing ','".
+    pub message: String,
+    /// How to fix the input, like "insert ','", which is also how the
+    /// output was fixed.
+    pub suggestion: Option<String>,
+}
+
+impl Diagnostic {
+    /// The diagnostic as a warning to render along with the input, with the
+    /// suggestion as a hint.
+    pub fn report(&self) -> Report {
+        let report = Report::warning(self.message.as_str())
+            .label(self.span.start.offset..self.span.end.offset, "");
+        match &self.suggestion {
+            Some(suggestion) => report.help(suggestion.as_str()),
+            None => report,
+        }
+    }
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} at {}", self.message, self.span.start)?;
+        if let Some(suggestion) = &self.suggestion {
+            write!(f, " ({})", suggestion)?;
+        }
+        Ok(())
+    }
+}
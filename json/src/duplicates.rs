@@ -0,0 +1,299 @@
+//! Finding keys that appear more than once in the same object, which RFC
+//! 8259 leaves undefined, and resolving them as asked by
+//! `Options::duplicate_keys`.
+//!
+//! Keeping the first member only needs the keys seen so far in each open
+//! object, but keeping the last one means earlier members may still have to
+//! be dropped, so then the members of each object are held back until it is
+//! closed.
+
+use crate::error::{Error, Position};
+use crate::options::DuplicateKeys;
+use crate::path::key_name;
+use crate::tokens::Token;
+use diagnostics::Report;
+use std::collections::HashMap;
+
+/// A key that appears more than once in the same object.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Duplicate {
+    /// The key, with its escapes decoded.
+    pub key: String,
+    /// Where the key first appears in the object.
+    pub first: Position,
+    /// Where it appears again.
+    pub duplicate: Position,
+}
+
+impl Duplicate {
+    /// The duplicate as a warning to render along with the input, pointing
+    /// at both keys.
+    pub fn report(&self) -> Report {
+        let (first, duplicate) = (self.first.offset, self.duplicate.offset);
+        Report::warning(format!("duplicate key {:?}", self.key))
+            .label(duplicate..duplicate, "")
+            .secondary_label(first..first, "first used here")
+    }
+}
+
+enum Scope {
+    Array,
+    Object {
+        /// Where each key first appears, and the index of its member in
+        /// `members`.
+        keys: HashMap<String, (Position, usize)>,
+        /// Whether the next token is a key.
+        expects_key: bool,
+        /// A comma that is held back until we know the next member is kept.
+        comma: Option<Position>,
+        /// The tokens of each member so far when keeping the last of several
+        /// members, `None` for members that have been dropped.
+        members: Vec<Option<Vec<(Token<'static>, Position)>>>,
+    },
+}
+
+pub(crate) struct Duplicates {
+    policy: DuplicateKeys,
+    stack: Vec<Scope>,
+    /// When dropping a member, how deep into its value we are.
+    skipping: Option<usize>,
+    found: Vec<Duplicate>,
+}
+
+impl Duplicates {
+    pub fn new(policy: DuplicateKeys) -> Self {
+        Duplicates {
+            policy,
+            stack: Vec::new(),
+            skipping: None,
+            found: Vec::new(),
+        }
+    }
+
+    /// Starts over at the beginning of a document, keeping what was found.
+    pub fn reset(&mut self) {
+        self.stack.clear();
+        self.skipping = None;
+    }
+
+    /// The duplicates found since the last call.
+    pub fn take_found(&mut self) -> Vec<Duplicate> {
+        std::mem::take(&mut self.found)
+    }
+
+    /// Processes the next token, which starts at `position`, and passes the
+    /// tokens that should be written to `emit`.
+    pub fn push<'a, E, F>(
+        &mut self,
+        token: Token<'a>,
+        position: Position,
+        mut emit: F,
+    ) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>, Position) -> Result<(), E>,
+    {
+        if let Some(depth) = self.skipping {
+            self.skipping = match token {
+                // The colon after the key
+                Token::Colon if depth == 0 => Some(0),
+                Token::ObjectStart | Token::ArrayStart => Some(depth + 1),
+                Token::ObjectEnd | Token::ArrayEnd if depth > 1 => Some(depth - 1),
+                Token::ObjectEnd | Token::ArrayEnd => None,
+                _ if depth == 0 => None,
+                _ => Some(depth),
+            };
+            return Ok(());
+        }
+
+        match (self.stack.last_mut(), token) {
+            (_, token @ Token::ObjectStart) => {
+                self.write(token, position, &mut emit)?;
+                self.stack.push(Scope::Object {
+                    keys: HashMap::new(),
+                    expects_key: true,
+                    comma: None,
+                    members: Vec::new(),
+                });
+                Ok(())
+            }
+            (_, token @ Token::ArrayStart) => {
+                self.write(token, position, &mut emit)?;
+                self.stack.push(Scope::Array);
+                Ok(())
+            }
+            (Some(Scope::Object { .. }), token @ Token::ObjectEnd) => {
+                if let Some(Scope::Object { members, .. }) = self.stack.pop() {
+                    // Only set when keeping the last member
+                    let members = members.into_iter().flatten();
+                    for (i, member) in members.enumerate() {
+                        if i > 0 {
+                            self.write(Token::Comma, member[0].1, &mut emit)?;
+                        }
+                        for (token, position) in member {
+                            self.write(token, position, &mut emit)?;
+                        }
+                    }
+                }
+                self.write(token, position, &mut emit)
+            }
+            (Some(Scope::Array), token @ Token::ArrayEnd) => {
+                self.stack.pop();
+                self.write(token, position, &mut emit)
+            }
+            (
+                Some(Scope::Object {
+                    expects_key, comma, ..
+                }),
+                Token::Comma,
+            ) => {
+                *expects_key = true;
+                *comma = Some(position);
+                Ok(())
+            }
+            (
+                Some(Scope::Object {
+                    expects_key: true, ..
+                }),
+                token,
+            ) => self.key(token, position, &mut emit),
+            (_, token) => self.write(token, position, &mut emit),
+        }
+    }
+
+    /// Handles the key of a new member.
+    fn key<'a, E, F>(&mut self, token: Token<'a>, position: Position, emit: &mut F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>, Position) -> Result<(), E>,
+    {
+        let (keys, comma, members) = match self.stack.last_mut() {
+            Some(Scope::Object {
+                keys,
+                expects_key,
+                comma,
+                members,
+            }) => {
+                *expects_key = false;
+                (keys, comma, members)
+            }
+            _ => unreachable!(),
+        };
+        let key = match key_name(&token) {
+            Some(key) => key,
+            // Only possible with lenient input
+            None => return self.write(token, position, emit),
+        };
+
+        match keys.get_mut(&key) {
+            Some((first, index)) => {
+                let duplicate = Duplicate {
+                    key,
+                    first: *first,
+                    duplicate: position,
+                };
+                *comma = None;
+                match self.policy {
+                    DuplicateKeys::Error => {
+                        return Err(Error::DuplicateKey(duplicate.key, position).into())
+                    }
+                    DuplicateKeys::KeepFirst => self.skipping = Some(0),
+                    DuplicateKeys::KeepLast => {
+                        members[*index] = None;
+                        *index = members.len();
+                        members.push(Some(vec![(token.into_owned(), position)]));
+                    }
+                    DuplicateKeys::Allow => unreachable!(),
+                }
+                self.found.push(duplicate);
+                Ok(())
+            }
+            None => {
+                keys.insert(key, (position, members.len()));
+                if self.policy == DuplicateKeys::KeepLast {
+                    *comma = None;
+                    members.push(Some(vec![(token.into_owned(), position)]));
+                    return Ok(());
+                }
+                if let Some(comma) = comma.take() {
+                    emit(Token::Comma, comma)?;
+                }
+                emit(token, position)
+            }
+        }
+    }
+
+    /// Passes on a token, or adds it to the member being read of the
+    /// innermost object when its members are held back.
+    fn write<'a, E, F>(
+        &mut self,
+        token: Token<'a>,
+        position: Position,
+        emit: &mut F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(Token<'a>, Position) -> Result<(), E>,
+    {
+        let innermost = self.stack.iter_mut().rev().find_map(|scope| match scope {
+            Scope::Object { members, .. } => Some(members),
+            Scope::Array => None,
+        });
+        match innermost.and_then(|members| members.last_mut()) {
+            Some(Some(member)) => {
+                member.push((token.into_owned(), position));
+                Ok(())
+            }
+            _ => emit(token, position),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tokens::Lexer;
+
+    fn resolve(source: &str, policy: DuplicateKeys) -> (String, Vec<Duplicate>) {
+        let mut duplicates = Duplicates::new(policy);
+        let mut lexer = Lexer::new(source);
+        let mut output = String::new();
+        loop {
+            let token = lexer.next_token().unwrap();
+            if token == Token::EndOfFile {
+                return (output, duplicates.take_found());
+            }
+            duplicates
+                .push(token, lexer.start(), |token, _| {
+                    output.push_str(&token.to_string());
+                    Ok::<_, Error>(())
+                })
+                .unwrap();
+        }
+    }
+
+    #[test]
+    fn policies() {
+        let source =
+            r#"{"a": 1, "b": {"a": [1, {"a": 2}], "c": 3, "a": {"x": []}}, "a": [4], "d": 5}"#;
+        let (first, found) = resolve(source, DuplicateKeys::KeepFirst);
+        assert_eq!(first, r#"{"a":1,"b":{"a":[1,{"a":2}],"c":3},"d":5}"#);
+        let keys = found.iter().map(|duplicate| &duplicate.key);
+        assert_eq!(keys.collect::<Vec<_>>(), ["a", "a"]);
+        assert_eq!(found[0].first.column, 16);
+        assert_eq!(found[0].duplicate.column, 44);
+        assert_eq!(found[1].first.column, 2);
+        assert_eq!(found[1].duplicate.column, 61);
+
+        let (last, found) = resolve(source, DuplicateKeys::KeepLast);
+        assert_eq!(last, r#"{"b":{"c":3,"a":{"x":[]}},"a":[4],"d":5}"#);
+        assert_eq!(found.len(), 2);
+
+        let (output, found) = resolve(
+            r#"[{"a": 1, "a": 2, "a": 3}, {"a": 4}]"#,
+            DuplicateKeys::KeepLast,
+        );
+        assert_eq!(output, r#"[{"a":3},{"a":4}]"#);
+        assert_eq!(found.len(), 2);
+        assert!(found.iter().all(|duplicate| duplicate.first.column == 3));
+    }
+}
@@ -0,0 +1,237 @@
+//! Detecting the encoding of JSON given as bytes and converting it to UTF-8,
+//! as RFC 8259 section 8.1 describes for text exchanged outside a closed
+//! ecosystem: UTF-8 is the norm, but a byte order mark or UTF-16 and UTF-32
+//! from older tools are common enough to accept.
+
+use crate::error::{Error, Position};
+use std::borrow::Cow;
+use std::fmt;
+use std::str;
+
+/// The encodings JSON text can be in.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Encoding {
+    Utf8,
+    Utf16Be,
+    Utf16Le,
+    Utf32Be,
+    Utf32Le,
+}
+
+impl Encoding {
+    /// The encoding of text starting with `bytes`, from its byte order mark
+    /// if it has one, and otherwise from where the zero bytes are in its
+    /// first character, which is ASCII in any JSON text.
+    pub fn detect(bytes: &[u8]) -> Self {
+        Encoding::with_bom(bytes).0
+    }
+
+    /// The encoding and the length of the byte order mark.
+    fn with_bom(bytes: &[u8]) -> (Self, usize) {
+        match bytes {
+            [0xef, 0xbb, 0xbf, ..] => (Encoding::Utf8, 3),
+            [0x00, 0x00, 0xfe, 0xff, ..] => (Encoding::Utf32Be, 4),
+            // Before UTF-16, whose mark starts the same
+            [0xff, 0xfe, 0x00, 0x00, ..] => (Encoding::Utf32Le, 4),
+            [0xfe, 0xff, ..] => (Encoding::Utf16Be, 2),
+            [0xff, 0xfe, ..] => (Encoding::Utf16Le, 2),
+            [0x00, 0x00, 0x00, _, ..] => (Encoding::Utf32Be, 0),
+            [_, 0x00, 0x00, 0x00, ..] => (Encoding::Utf32Le, 0),
+            [0x00, _, ..] => (Encoding::Utf16Be, 0),
+            [_, 0x00, ..] => (Encoding::Utf16Le, 0),
+            _ => (Encoding::Utf8, 0),
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Utf16Be => "UTF-16BE",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf32Be => "UTF-32BE",
+            Encoding::Utf32Le => "UTF-32LE",
+        })
+    }
+}
+
+/// Converts JSON text in any of the `Encoding`s to UTF-8 without a byte
+/// order mark, borrowing it when it is UTF-8 already.
+///
+/// Positions in errors, here and when minifying the result, are in the
+/// UTF-8 text.
+pub fn decode(bytes: &[u8]) -> Result<Cow<'_, str>, Error> {
+    transcode(bytes, false)
+}
+
+/// Like `decode`, but replaces invalid sequences with U+FFFD instead of
+/// failing. Up to the first of them the text is the same as `decode` would
+/// return, so it can be shown along with a position from an
+/// `Error::InvalidEncoding`.
+pub fn decode_lossy(bytes: &[u8]) -> Cow<'_, str> {
+    transcode(bytes, true).unwrap()
+}
+
+fn transcode(bytes: &[u8], lossy: bool) -> Result<Cow<'_, str>, Error> {
+    let (encoding, bom) = Encoding::with_bom(bytes);
+    let bytes = &bytes[bom..];
+    // Either replaces an invalid sequence or fails at it
+    let invalid = |text: &mut String| {
+        if lossy {
+            text.push(char::REPLACEMENT_CHARACTER);
+            Ok(())
+        } else {
+            Err(Error::InvalidEncoding(encoding, end_of(text)))
+        }
+    };
+    match encoding {
+        Encoding::Utf8 if lossy => Ok(String::from_utf8_lossy(bytes)),
+        Encoding::Utf8 => match str::from_utf8(bytes) {
+            Ok(text) => Ok(text.into()),
+            Err(error) => {
+                let valid = str::from_utf8(&bytes[..error.valid_up_to()]).unwrap();
+                Err(Error::InvalidEncoding(encoding, end_of(valid)))
+            }
+        },
+        Encoding::Utf16Be | Encoding::Utf16Le => {
+            let big_endian = encoding == Encoding::Utf16Be;
+            let units = bytes.chunks(2).map(|unit| match unit {
+                [a, b] if big_endian => Some(u16::from_be_bytes([*a, *b])),
+                [a, b] => Some(u16::from_le_bytes([*a, *b])),
+                // Half a code unit at the end
+                _ => None,
+            });
+            let mut text = String::with_capacity(bytes.len() / 2);
+            for c in char::decode_utf16(units.map(|unit| unit.unwrap_or(0xdc00))) {
+                match c {
+                    Ok(c) => text.push(c),
+                    Err(_) => invalid(&mut text)?,
+                }
+            }
+            Ok(text.into())
+        }
+        Encoding::Utf32Be | Encoding::Utf32Le => {
+            let mut text = String::with_capacity(bytes.len() / 4);
+            for unit in bytes.chunks(4) {
+                let c = match unit {
+                    [a, b, c, d] if encoding == Encoding::Utf32Be => {
+                        u32::from_be_bytes([*a, *b, *c, *d])
+                    }
+                    [a, b, c, d] => u32::from_le_bytes([*a, *b, *c, *d]),
+                    _ => u32::MAX,
+                };
+                match char::from_u32(c) {
+                    Some(c) => text.push(c),
+                    None => invalid(&mut text)?,
+                }
+            }
+            Ok(text.into())
+        }
+    }
+}
+
+/// The position right after `text`.
+fn end_of(text: &str) -> Position {
+    let mut position = Position::default();
+    text.chars().for_each(|c| position.advance(c));
+    position
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn utf16(text: &str, big_endian: bool) -> Vec<u8> {
+        text.encode_utf16()
+            .flat_map(|unit| {
+                if big_endian {
+                    unit.to_be_bytes()
+                } else {
+                    unit.to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    fn utf32(text: &str, big_endian: bool) -> Vec<u8> {
+        text.chars()
+            .flat_map(|c| {
+                if big_endian {
+                    (c as u32).to_be_bytes()
+                } else {
+                    (c as u32).to_le_bytes()
+                }
+            })
+            .collect()
+    }
+
+    #[test]
+    fn encodings() {
+        let text = "{\"é\": [\"😀\"]}";
+        let with_bom = |bytes: Vec<u8>, bom: &[u8]| [bom, &bytes].concat();
+        let cases = [
+            (text.as_bytes().to_vec(), Encoding::Utf8),
+            (with_bom(text.into(), &[0xef, 0xbb, 0xbf]), Encoding::Utf8),
+            (utf16(text, true), Encoding::Utf16Be),
+            (utf16(text, false), Encoding::Utf16Le),
+            (
+                with_bom(utf16(text, true), &[0xfe, 0xff]),
+                Encoding::Utf16Be,
+            ),
+            (
+                with_bom(utf16(text, false), &[0xff, 0xfe]),
+                Encoding::Utf16Le,
+            ),
+            (utf32(text, true), Encoding::Utf32Be),
+            (utf32(text, false), Encoding::Utf32Le),
+            (
+                with_bom(utf32(text, true), &[0, 0, 0xfe, 0xff]),
+                Encoding::Utf32Be,
+            ),
+            (
+                with_bom(utf32(text, false), &[0xff, 0xfe, 0, 0]),
+                Encoding::Utf32Le,
+            ),
+        ];
+        for (bytes, encoding) in &cases {
+            assert_eq!(Encoding::detect(bytes), *encoding, "{:?}", bytes);
+            assert_eq!(decode(bytes).unwrap(), text, "{}", encoding);
+        }
+        assert!(matches!(decode(text.as_bytes()), Ok(Cow::Borrowed(_))));
+
+        // Short documents
+        assert_eq!(decode(&utf16("1", true)).unwrap(), "1");
+        assert_eq!(decode(&utf16("1", false)).unwrap(), "1");
+        assert_eq!(decode(b"").unwrap(), "");
+    }
+
+    #[test]
+    fn invalid() {
+        let invalid = |bytes: &[u8], encoding, column| match decode(bytes) {
+            Err(Error::InvalidEncoding(e, position)) => {
+                assert_eq!((e, position.column), (encoding, column), "{:?}", bytes)
+            }
+            result => panic!("{:?}: expected an error, got {:?}", bytes, result),
+        };
+        invalid(b"[\"a\xff\"]", Encoding::Utf8, 4);
+        invalid(b"\xef\xbb\xbf[\xc3]", Encoding::Utf8, 2);
+        // An unpaired surrogate, and half a code unit at the end
+        invalid(&[0, b'[', 0xd8, 0x00, 0, b']'], Encoding::Utf16Be, 2);
+        invalid(&[b'[', 0, b'1', 0, b']'], Encoding::Utf16Le, 3);
+        invalid(&[b'[', 0, 0, 0, 0, 0, 0x11, 0], Encoding::Utf32Le, 2);
+    }
+
+    #[test]
+    fn lossy() {
+        let bytes = utf16("[\"éa", false);
+        let bytes = [&bytes[..], &[0x00, 0xd8], &utf16("\"]", false)].concat();
+        let position = decode(&bytes).unwrap_err().position();
+        let text = decode_lossy(&bytes);
+        assert_eq!(text, "[\"éa\u{fffd}\"]");
+        assert_eq!(&text[position.offset..], "\u{fffd}\"]");
+
+        assert_eq!(decode_lossy(b"[\xff]"), "[\u{fffd}]");
+        assert!(matches!(decode_lossy(b"[1]"), Cow::Borrowed("[1]")));
+    }
+}
@@ -0,0 +1,202 @@
+use crate::encoding::Encoding;
+use diagnostics::Report;
+use std::error;
+use std::fmt;
+use std::io;
+
+/// A location in the input.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Position {
+    /// Line number, starting at 1.
+    pub line: usize,
+    /// Column in characters, starting at 1.
+    pub column: usize,
+    /// Byte offset from the start of the input.
+    pub offset: usize,
+}
+
+impl Position {
+    pub(crate) fn advance(&mut self, c: char) {
+        self.offset += c.len_utf8();
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+    }
+}
+
+impl Default for Position {
+    fn default() -> Self {
+        Position {
+            line: 1,
+            column: 1,
+            offset: 0,
+        }
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// Which of the `Limits` was exceeded.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Limit {
+    Depth,
+    StringBytes,
+    NumberLength,
+    Tokens,
+    InputBytes,
+}
+
+impl fmt::Display for Limit {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            Limit::Depth => "nesting depth",
+            Limit::StringBytes => "string length",
+            Limit::NumberLength => "number length",
+            Limit::Tokens => "number of tokens",
+            Limit::InputBytes => "input size",
+        })
+    }
+}
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Error {
+    /// Bytes that are not valid in the encoding of the input, starting at
+    /// this position in the input converted to UTF-8.
+    InvalidEncoding(Encoding, Position),
+    /// A character that cannot start or continue a token.
+    UnexpectedCharacter(char, Position),
+    /// The input ended before the closing quote of the string starting here.
+    UnterminatedString(Position),
+    /// An unknown escape sequence or malformed `\uXXXX` escape.
+    InvalidEscape(Position),
+    /// A `\uXXXX` escape of a surrogate that is not part of a valid pair.
+    UnpairedSurrogate(Position),
+    /// The input ended inside the block comment starting here.
+    UnterminatedComment(Position),
+    /// A raw control character inside a string, these have to be escaped.
+    ControlCharacter(Position),
+    /// A number that does not follow the JSON number grammar.
+    InvalidNumber(Position),
+    /// A JSON5 `Infinity` or `NaN`, which cannot be represented in JSON.
+    NonFiniteNumber(Position),
+    /// A number too large to be represented as a double, which canonical
+    /// output requires.
+    NumberOutOfRange(Position),
+    /// A key that already appeared in the same object, when duplicate keys
+    /// are not allowed.
+    DuplicateKey(String, Position),
+    /// The input goes over one of the `Limits`, at the token starting here.
+    LimitExceeded(Limit, Position),
+    /// The input ended in the middle of a token or before the document was
+    /// complete.
+    UnexpectedEof(Position),
+    /// A token that is not allowed by the JSON grammar at this point.
+    UnexpectedToken(&'static str, Position),
+    /// Two options that cannot be used together, reported at the start of
+    /// the input.
+    ConflictingOptions(&'static str, &'static str),
+}
+
+impl Error {
+    /// Where in the input the error occurred.
+    pub fn position(&self) -> Position {
+        match *self {
+            Error::InvalidEncoding(_, position)
+            | Error::UnexpectedCharacter(_, position)
+            | Error::UnterminatedString(position)
+            | Error::InvalidEscape(position)
+            | Error::UnpairedSurrogate(position)
+            | Error::UnterminatedComment(position)
+            | Error::ControlCharacter(position)
+            | Error::InvalidNumber(position)
+            | Error::NonFiniteNumber(position)
+            | Error::NumberOutOfRange(position)
+            | Error::DuplicateKey(_, position)
+            | Error::LimitExceeded(_, position)
+            | Error::UnexpectedEof(position)
+            | Error::UnexpectedToken(_, position) => position,
+            Error::ConflictingOptions(..) => Position::default(),
+        }
+    }
+
+    /// What went wrong, without the position.
+    pub fn message(&self) -> String {
+        let mut message = String::new();
+        self.write_message(&mut message).unwrap();
+        message
+    }
+
+    /// The error as a report to render along with the input, with a hint
+    /// on how to fix it where there is one.
+    pub fn report(&self) -> Report {
+        let offset = self.position().offset;
+        let report = match self {
+            // Nothing in the input to point at
+            Error::ConflictingOptions(..) => Report::error(self.message()),
+            _ => Report::error(self.message()).label(offset..offset, ""),
+        };
+        match self.hint() {
+            Some(hint) => report.help(hint),
+            None => report,
+        }
+    }
+
+    fn hint(&self) -> Option<&'static str> {
+        match self {
+            Error::UnexpectedCharacter('\'', _) => Some("JSON strings use double quotes"),
+            Error::UnterminatedString(_) => Some("add the closing quote"),
+            Error::InvalidEscape(_) => {
+                Some("the escapes are \\\" \\\\ \\/ \\b \\f \\n \\r \\t and \\uXXXX")
+            }
+            Error::UnterminatedComment(_) => Some("close the comment with `*/`"),
+            Error::ControlCharacter(_) => Some("escape it, like `\\n` for a line break"),
+            Error::NonFiniteNumber(_) => Some("use null or a string instead"),
+            Error::DuplicateKey(..) => Some("keep only one of the members"),
+            _ => None,
+        }
+    }
+
+    fn write_message<W: fmt::Write>(&self, f: &mut W) -> fmt::Result {
+        match self {
+            Error::InvalidEncoding(encoding, _) => write!(f, "invalid {}", encoding),
+            Error::UnexpectedCharacter(c, _) => write!(f, "unexpected character {:?}", c),
+            Error::UnterminatedString(_) => f.write_str("unterminated string"),
+            Error::InvalidEscape(_) => f.write_str("invalid escape sequence"),
+            Error::UnpairedSurrogate(_) => f.write_str("unpaired surrogate in unicode escape"),
+            Error::UnterminatedComment(_) => f.write_str("unterminated comment"),
+            Error::ControlCharacter(_) => f.write_str("unescaped control character in string"),
+            Error::InvalidNumber(_) => f.write_str("invalid number"),
+            Error::NonFiniteNumber(_) => f.write_str("Infinity and NaN are not valid JSON"),
+            Error::NumberOutOfRange(_) => f.write_str("number is too large for a double"),
+            Error::DuplicateKey(key, _) => write!(f, "duplicate key {:?}", key),
+            Error::LimitExceeded(limit, _) => write!(f, "{} limit exceeded", limit),
+            Error::UnexpectedEof(_) => f.write_str("unexpected end of input"),
+            Error::UnexpectedToken(token, _) => write!(f, "unexpected {}", token),
+            Error::ConflictingOptions(a, b) => {
+                write!(f, "{} cannot be used with {}", a, b)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_message(f)?;
+        write!(f, " at {}", self.position())
+    }
+}
+
+impl error::Error for Error {}
+
+impl From<Error> for io::Error {
+    fn from(error: Error) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, error)
+    }
+}
@@ -0,0 +1,172 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Decodes the escapes in the body of a string the lexer has validated,
+/// including those only JSON5 allows, which the lexer lets through in JSON5
+/// input only.
+pub(crate) fn unescape(raw: &str) -> Cow<'_, str> {
+    if !raw.contains('\\') {
+        return raw.into();
+    }
+
+    let mut string = String::with_capacity(raw.len());
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            string.push(c);
+            continue;
+        }
+
+        match chars.next() {
+            Some('b') => string.push('\u{8}'),
+            Some('f') => string.push('\u{c}'),
+            Some('n') => string.push('\n'),
+            Some('r') => string.push('\r'),
+            Some('t') => string.push('\t'),
+            Some('u') => {
+                let mut unit = hex(&mut chars, 4);
+                // The lexer only lets through high surrogates that are
+                // followed by an escaped low surrogate
+                if (0xD800..0xDC00).contains(&unit) {
+                    chars.nth(1);
+                    let low = hex(&mut chars, 4);
+                    unit = 0x10000 + ((unit - 0xD800) << 10) + (low - 0xDC00);
+                }
+                string.push(std::char::from_u32(unit).unwrap_or('\u{fffd}'));
+            }
+            Some('x') => string.push(std::char::from_u32(hex(&mut chars, 2)).unwrap()),
+            Some('v') => string.push('\u{b}'),
+            Some('0') => string.push('\0'),
+            // Escaped line breaks are line continuations and not part of the
+            // string, `\r\n` counts as one line break
+            Some('\r') if chars.as_str().starts_with('\n') => {
+                chars.next();
+            }
+            Some('\r') | Some('\n') | Some('\u{2028}') | Some('\u{2029}') => {}
+            Some(c) => string.push(c),
+            None => {}
+        }
+    }
+
+    string.into()
+}
+
+fn hex(chars: &mut std::str::Chars, digits: usize) -> u32 {
+    chars
+        .take(digits)
+        .fold(0, |unit, c| unit * 16 + c.to_digit(16).unwrap_or(0))
+}
+
+/// Escapes `string` for the body of a JSON string with as few escapes as
+/// possible: only `"`, `\` and control characters are escaped, using the
+/// two character forms where they exist and lowercase `\u00xx` otherwise.
+pub(crate) fn escape(string: &str) -> Cow<'_, str> {
+    if !string.contains(|c| c == '"' || c == '\\' || c < '\u{20}') {
+        return string.into();
+    }
+
+    let mut escaped = String::with_capacity(string.len() + 2);
+    for c in string.chars() {
+        push_escaped(&mut escaped, c, '"');
+    }
+
+    escaped.into()
+}
+
+/// Adds `c` to the body of a string quoted with `quote`, escaped only if it
+/// has to be.
+pub(crate) fn push_escaped(string: &mut String, c: char, quote: char) {
+    match c {
+        '\\' => string.push_str("\\\\"),
+        c if c == quote => {
+            string.push('\\');
+            string.push(c);
+        }
+        '\u{8}' => string.push_str("\\b"),
+        '\u{c}' => string.push_str("\\f"),
+        '\n' => string.push_str("\\n"),
+        '\r' => string.push_str("\\r"),
+        '\t' => string.push_str("\\t"),
+        c if c < '\u{20}' => write!(string, "\\u{:04x}", c as u32).unwrap(),
+        c => string.push(c),
+    }
+}
+
+/// Escapes every non-ASCII character in the body of a string as `\uXXXX`,
+/// using a surrogate pair for characters outside the Basic Multilingual
+/// Plane.
+pub(crate) fn escape_non_ascii(string: Cow<str>) -> Cow<str> {
+    if string.is_ascii() {
+        return string;
+    }
+
+    let mut escaped = String::with_capacity(string.len() * 2);
+    for c in string.chars() {
+        if c.is_ascii() {
+            escaped.push(c);
+            continue;
+        }
+        for unit in c.encode_utf16(&mut [0; 2]) {
+            write!(escaped, "\\u{:04x}", unit).unwrap();
+        }
+    }
+
+    escaped.into()
+}
+
+/// Escapes `<`, `>`, `&`, U+2028 and U+2029 in the body of a string, so it
+/// cannot close a `<script>` element, open an HTML comment or end a line in
+/// JavaScript source.
+pub(crate) fn escape_html(string: Cow<str>) -> Cow<str> {
+    let unsafe_char = |c| matches!(c, '<' | '>' | '&' | '\u{2028}' | '\u{2029}');
+    if !string.contains(unsafe_char) {
+        return string;
+    }
+
+    let mut escaped = String::with_capacity(string.len() + 10);
+    for c in string.chars() {
+        if unsafe_char(c) {
+            write!(escaped, "\\u{:04x}", c as u32).unwrap();
+        } else {
+            escaped.push(c);
+        }
+    }
+
+    escaped.into()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn round_trip() {
+        let cases = [
+            (r#"plain"#, "plain"),
+            (r#"\"\\\/\b\f\n\r\t"#, "\"\\/\u{8}\u{c}\n\r\t"),
+            (r#"\u0041\u00e9\u20AC"#, "Aé€"),
+            (r#"\uD83D\ude00!"#, "😀!"),
+            (r#"\u0000\u001F"#, "\u{0}\u{1f}"),
+            // JSON5
+            (r#"\x61\v\0\'"#, "a\u{b}\0'"),
+            ("a\\\r\nb\\\nc\\\rd\\\u{2028}e", "abcde"),
+        ];
+        for &(raw, decoded) in &cases {
+            assert_eq!(unescape(raw), decoded);
+        }
+
+        assert_eq!(escape("Aé€😀/\u{2028}"), "Aé€😀/\u{2028}");
+        assert_eq!(
+            escape_html("</script><!-- a && b -->\u{2028}\u{2029}".into()),
+            r#"\u003c/script\u003e\u003c!-- a \u0026\u0026 b --\u003e\u2028\u2029"#
+        );
+        assert_eq!(
+            escape_non_ascii(r#"Aé€😀\n\u0041"#.into()),
+            r#"A\u00e9\u20ac\ud83d\ude00\n\u0041"#
+        );
+        assert_eq!(
+            escape("\"\\\u{8}\u{c}\n\r\t\u{0}\u{1f}"),
+            r#"\"\\\b\f\n\r\t\u0000\u001f"#
+        );
+    }
+}
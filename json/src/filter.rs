@@ -0,0 +1,316 @@
+//! Dropping object members as asked by `Options::drop_nulls`,
+//! `Options::drop_empty`, `Options::drop_keys` and `Options::drop_paths`.
+//!
+//! Whether a member is dropped is not always known when its key is read: a
+//! member whose value is an object is dropped if every member of that object
+//! is. So the tokens of a member are held back until something in it is
+//! kept, and commas are written again for the members that remain.
+
+use crate::error::Position;
+use crate::path::{key_name, Path, Pattern};
+use crate::tokens::Token;
+
+struct Scope {
+    object: bool,
+    /// Whether anything in the scope has been written, so the next member
+    /// or element needs a comma.
+    written: bool,
+    /// Whether the next token is a key.
+    expects_key: bool,
+    /// The length of `pending` before the current member or element.
+    mark: usize,
+}
+
+pub(crate) struct Filter {
+    drop_nulls: bool,
+    drop_empty: bool,
+    keys: Vec<String>,
+    /// Where we are in the input, when dropping members by location.
+    path: Option<Path>,
+    patterns: Vec<Pattern>,
+    stack: Vec<Scope>,
+    /// Tokens held back until we know whether they are kept.
+    pending: Vec<(Token<'static>, Position)>,
+    /// When dropping a member, how deep into its value we are.
+    skipping: Option<usize>,
+}
+
+impl Filter {
+    pub fn new(drop_nulls: bool, drop_empty: bool, keys: &[String], paths: &[String]) -> Self {
+        Filter {
+            drop_nulls,
+            drop_empty,
+            keys: keys.to_vec(),
+            path: if paths.is_empty() {
+                None
+            } else {
+                Some(Path::default())
+            },
+            patterns: paths.iter().map(|pointer| Pattern::new(pointer)).collect(),
+            stack: Vec::new(),
+            pending: Vec::new(),
+            skipping: None,
+        }
+    }
+
+    /// Starts over at the beginning of a document.
+    pub fn reset(&mut self) {
+        if let Some(path) = &mut self.path {
+            path.reset();
+        }
+        self.stack.clear();
+        self.pending.clear();
+        self.skipping = None;
+    }
+
+    /// Processes the next token, which starts at `position`, and passes the
+    /// tokens that should be written to `emit`.
+    pub fn push<'a, E, F>(
+        &mut self,
+        token: Token<'a>,
+        position: Position,
+        mut emit: F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(Token<'a>, Position) -> Result<(), E>,
+    {
+        if let Some(path) = &mut self.path {
+            path.push(&token);
+        }
+        if let Some(depth) = self.skipping {
+            self.skipping = match token {
+                // The colon after the key
+                Token::Colon if depth == 0 => Some(0),
+                Token::ObjectStart | Token::ArrayStart => Some(depth + 1),
+                Token::ObjectEnd | Token::ArrayEnd if depth > 1 => Some(depth - 1),
+                Token::ObjectEnd | Token::ArrayEnd => None,
+                _ if depth == 0 => None,
+                _ => Some(depth),
+            };
+            return Ok(());
+        }
+
+        let scope = match self.stack.last_mut() {
+            Some(scope) => scope,
+            None => {
+                if let Token::ObjectStart | Token::ArrayStart = token {
+                    self.open(token, position);
+                    return Ok(());
+                }
+                return emit(token, position);
+            }
+        };
+        match token {
+            Token::Comma => {
+                scope.expects_key = scope.object;
+                Ok(())
+            }
+            Token::Colon => {
+                self.pending.push((Token::Colon, position));
+                Ok(())
+            }
+            Token::ObjectEnd | Token::ArrayEnd => self.close(token, position, &mut emit),
+            token if scope.expects_key => {
+                scope.expects_key = false;
+                let dropped = key_name(&token).is_some_and(|key| self.keys.contains(&key));
+                if dropped || self.in_patterns() {
+                    self.skipping = Some(0);
+                    return Ok(());
+                }
+                self.start(position);
+                self.pending.push((token.into_owned(), position));
+                Ok(())
+            }
+            token => {
+                if !scope.object {
+                    self.start(position);
+                }
+                self.value(token, position, &mut emit)
+            }
+        }
+    }
+
+    /// Handles a value, after its key or comma has been added to `pending`.
+    fn value<'a, E, F>(
+        &mut self,
+        token: Token<'a>,
+        position: Position,
+        emit: &mut F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(Token<'a>, Position) -> Result<(), E>,
+    {
+        match token {
+            Token::ObjectStart | Token::ArrayStart => {
+                self.open(token, position);
+                Ok(())
+            }
+            Token::Null if self.drop_nulls && self.in_object() => {
+                self.drop_member();
+                Ok(())
+            }
+            token => {
+                self.flush(emit)?;
+                emit(token, position)
+            }
+        }
+    }
+
+    /// Starts a new member or element, with a comma before it if it is not
+    /// the first one written.
+    fn start(&mut self, position: Position) {
+        let scope = self.stack.last_mut().unwrap();
+        scope.mark = self.pending.len();
+        if scope.written {
+            self.pending.push((Token::Comma, position));
+        }
+    }
+
+    fn open(&mut self, token: Token, position: Position) {
+        self.stack.push(Scope {
+            object: token == Token::ObjectStart,
+            written: false,
+            expects_key: token == Token::ObjectStart,
+            mark: 0,
+        });
+        self.pending.push((token.into_owned(), position));
+    }
+
+    fn close<'a, E, F>(
+        &mut self,
+        token: Token<'a>,
+        position: Position,
+        emit: &mut F,
+    ) -> Result<(), E>
+    where
+        F: FnMut(Token<'a>, Position) -> Result<(), E>,
+    {
+        let scope = self.stack.pop().unwrap();
+        if !scope.written && self.drop_empty && self.in_object() {
+            self.drop_member();
+            return Ok(());
+        }
+
+        self.flush(emit)?;
+        emit(token, position)
+    }
+
+    /// Drops the tokens of the member being read in the innermost object.
+    fn drop_member(&mut self) {
+        if let Some(scope) = self.stack.last() {
+            self.pending.truncate(scope.mark);
+        }
+    }
+
+    /// Writes the tokens held back, since something after them is kept.
+    fn flush<'a, E, F>(&mut self, emit: &mut F) -> Result<(), E>
+    where
+        F: FnMut(Token<'a>, Position) -> Result<(), E>,
+    {
+        for (token, position) in self.pending.drain(..) {
+            emit(token, position)?;
+        }
+        for scope in &mut self.stack {
+            scope.written = true;
+        }
+
+        Ok(())
+    }
+
+    fn in_object(&self) -> bool {
+        self.stack.last().is_some_and(|scope| scope.object)
+    }
+
+    fn in_patterns(&self) -> bool {
+        match &self.path {
+            Some(path) => self.patterns.iter().any(|pattern| path.within(pattern)),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tokens::Lexer;
+
+    fn filter(source: &str, mut filter: Filter) -> String {
+        let mut output = String::new();
+        for item in Lexer::new(source) {
+            let (token, span) = item.unwrap();
+            filter
+                .push(token, span.start, |token, _| {
+                    output.push_str(&token.to_string());
+                    Ok::<_, ()>(())
+                })
+                .unwrap();
+        }
+        output
+    }
+
+    fn strings(values: &[&str]) -> Vec<String> {
+        values.iter().map(|value| value.to_string()).collect()
+    }
+
+    #[test]
+    fn nulls() {
+        let nulls = || Filter::new(true, false, &[], &[]);
+        assert_eq!(
+            filter(r#"{"a": null, "b": 1, "c": null, "d": 2}"#, nulls()),
+            r#"{"b":1,"d":2}"#
+        );
+        assert_eq!(filter(r#"{"a": null}"#, nulls()), "{}");
+        // Elements are kept, so indices do not change
+        assert_eq!(filter(r#"[null, {"a": null}]"#, nulls()), "[null,{}]");
+        assert_eq!(filter("null", nulls()), "null");
+    }
+
+    #[test]
+    fn empty() {
+        let empty = || Filter::new(true, true, &[], &[]);
+        // Emptiness goes up through objects, but not through arrays
+        assert_eq!(
+            filter(
+                r#"{"a": [[], {}], "b": {"c": [{}]}, "d": {"e": {"f": null}}, "g": []}"#,
+                empty()
+            ),
+            r#"{"a":[[],{}],"b":{"c":[{}]}}"#
+        );
+        assert_eq!(filter(r#"{"a": {"b": {}}}"#, empty()), "{}");
+        assert_eq!(filter("[]", empty()), "[]");
+    }
+
+    #[test]
+    fn keys_and_paths() {
+        let source = r#"{"a": {"x": 1, "y": 2}, "b": {"x": 3}, "x": 4, "c": {"y": {"x": 5}}}"#;
+        let keys = strings(&["x"]);
+        // Members matched by both are only dropped once
+        let paths = strings(&["/a/x", "/b"]);
+        assert_eq!(
+            filter(source, Filter::new(false, false, &keys, &paths)),
+            r#"{"a":{"y":2},"c":{"y":{}}}"#
+        );
+        assert_eq!(
+            filter(source, Filter::new(false, true, &keys, &paths)),
+            r#"{"a":{"y":2}}"#
+        );
+    }
+
+    #[test]
+    fn wildcards() {
+        let source = r#"{"items": [{"a": 1, "b": 2}, {"a": 3}], "a": 4}"#;
+        let paths = strings(&["/items/*/a"]);
+        assert_eq!(
+            filter(source, Filter::new(false, false, &[], &paths)),
+            r#"{"items":[{"b":2},{}],"a":4}"#
+        );
+        // Elements are never dropped, so a location of one only empties it
+        let paths = strings(&["/items/0"]);
+        assert_eq!(
+            filter(source, Filter::new(false, false, &[], &paths)),
+            r#"{"items":[{},{"a":3}],"a":4}"#
+        );
+        let paths = strings(&["/*"]);
+        assert_eq!(filter(source, Filter::new(false, false, &[], &paths)), "{}");
+    }
+}
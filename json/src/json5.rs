@@ -0,0 +1,223 @@
+use crate::error::{Error, Position};
+use crate::tokens::Token;
+use std::borrow::Cow;
+use std::fmt::Write;
+
+/// Whether a JSON5 token can be used as an object key.
+pub(crate) fn is_key(token: &Token) -> bool {
+    match token {
+        Token::String(_)
+        | Token::SingleQuoted(_)
+        | Token::Identifier(_)
+        | Token::Null
+        | Token::True
+        | Token::False => true,
+        Token::Number(number) => number == "Infinity" || number == "NaN",
+        _ => false,
+    }
+}
+
+/// Converts a JSON5 token starting at `position` to its strict JSON
+/// equivalent, `key` tells whether the token is used as an object key.
+pub(crate) fn to_json(token: Token, key: bool, position: Position) -> Result<Token, Error> {
+    let token = match token {
+        Token::String(string) | Token::SingleQuoted(string) => {
+            Token::String(string_to_json(string))
+        }
+        Token::Identifier(name) => Token::String(name),
+        Token::Null if key => Token::String("null".into()),
+        Token::True if key => Token::String("true".into()),
+        Token::False if key => Token::String("false".into()),
+        Token::Number(number) if key => Token::String(number),
+        Token::Number(number) => Token::Number(number_to_json(&number, position)?.into()),
+        token => token,
+    };
+
+    Ok(token)
+}
+
+/// Rewrites the body of a JSON5 string with only the escapes JSON allows.
+fn string_to_json(string: Cow<str>) -> Cow<str> {
+    if !string.contains(|c| c == '\\' || c == '"' || c < '\u{20}') {
+        return string;
+    }
+
+    let mut json = String::with_capacity(string.len());
+    let mut chars = string.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            push_json_char(&mut json, c);
+            continue;
+        }
+
+        match chars.next() {
+            Some(c) if "\"\\/bfnrtu".contains(c) => {
+                json.push('\\');
+                json.push(c);
+            }
+            Some('v') => json.push_str("\\u000b"),
+            Some('0') => json.push_str("\\u0000"),
+            Some('x') => {
+                json.push_str("\\u00");
+                json.extend(chars.by_ref().take(2));
+            }
+            // Escaped line breaks are line continuations and not part of
+            // the string, `\r\n` counts as one line break
+            Some('\r') if chars.as_str().starts_with('\n') => {
+                chars.next();
+            }
+            Some('\r') | Some('\n') | Some('\u{2028}') | Some('\u{2029}') => {}
+            Some(c) => push_json_char(&mut json, c),
+            None => {}
+        }
+    }
+
+    json.into()
+}
+
+fn push_json_char(json: &mut String, c: char) {
+    match c {
+        '"' => json.push_str("\\\""),
+        '\u{8}' => json.push_str("\\b"),
+        '\u{c}' => json.push_str("\\f"),
+        '\n' => json.push_str("\\n"),
+        '\r' => json.push_str("\\r"),
+        '\t' => json.push_str("\\t"),
+        c if c < '\u{20}' => write!(json, "\\u{:04x}", c as u32).unwrap(),
+        c => json.push(c),
+    }
+}
+
+/// Rewrites a JSON5 number in JSON number syntax.
+fn number_to_json(number: &str, position: Position) -> Result<String, Error> {
+    let (negative, unsigned) = match number.as_bytes()[0] {
+        b'-' => (true, &number[1..]),
+        b'+' => (false, &number[1..]),
+        _ => (false, number),
+    };
+    if unsigned == "Infinity" || unsigned == "NaN" {
+        return Err(Error::NonFiniteNumber(position));
+    }
+
+    let mut json = String::new();
+    if negative {
+        json.push('-');
+    }
+
+    if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        json.push_str(&hex_to_decimal(hex));
+        return Ok(json);
+    }
+
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(e) => unsigned.split_at(e),
+        None => (unsigned, ""),
+    };
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(dot) => (&mantissa[..dot], &mantissa[dot + 1..]),
+        None => (mantissa, ""),
+    };
+    json.push_str(if integer.is_empty() { "0" } else { integer });
+    if !fraction.is_empty() {
+        json.push('.');
+        json.push_str(fraction);
+    }
+    json.push_str(exponent);
+
+    Ok(json)
+}
+
+/// Converts hexadecimal digits to decimal without any size limit.
+fn hex_to_decimal(hex: &str) -> String {
+    // Decimal digits, least significant first
+    let mut digits: Vec<u32> = Vec::new();
+    for c in hex.chars() {
+        let mut carry = c.to_digit(16).unwrap();
+        for digit in digits.iter_mut() {
+            let value = *digit * 16 + carry;
+            *digit = value % 10;
+            carry = value / 10;
+        }
+        while carry > 0 {
+            digits.push(carry % 10);
+            carry /= 10;
+        }
+    }
+
+    if digits.is_empty() {
+        return "0".to_string();
+    }
+    digits
+        .iter()
+        .rev()
+        .map(|&digit| std::char::from_digit(digit, 10).unwrap())
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn strings() {
+        assert_eq!(string_to_json(r#"say "hi""#.into()), r#"say \"hi\""#);
+        assert_eq!(string_to_json(r#"it\'s"#.into()), "it's");
+        assert_eq!(
+            string_to_json(r#"\"\\\/\b\f\n\r\té"#.into()),
+            r#"\"\\\/\b\f\n\r\té"#
+        );
+        assert_eq!(
+            string_to_json(r#"\v\0\x41\a"#.into()),
+            r#"\u000b\u0000\u0041a"#
+        );
+        assert_eq!(string_to_json("a\\\nb\\\r\nc\\\u{2028}d".into()), "abcd");
+        assert_eq!(
+            string_to_json("tab\there\u{1}".into()),
+            r#"tab\there\u0001"#
+        );
+    }
+
+    #[test]
+    fn numbers() {
+        let json = |number| number_to_json(number, Position::default()).unwrap();
+        assert_eq!(json("+1"), "1");
+        assert_eq!(json(".5"), "0.5");
+        assert_eq!(json("-.5e3"), "-0.5e3");
+        assert_eq!(json("5."), "5");
+        assert_eq!(json("5.E-3"), "5E-3");
+        assert_eq!(json("0xdecaf"), "912559");
+        assert_eq!(json("-0X1F"), "-31");
+        assert_eq!(json("0x0"), "0");
+        assert_eq!(
+            json("0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"),
+            "340282366920938463463374607431768211455"
+        );
+        for number in &["Infinity", "-Infinity", "+NaN"] {
+            assert_eq!(
+                number_to_json(number, Position::default()),
+                Err(Error::NonFiniteNumber(Position::default()))
+            );
+        }
+    }
+
+    #[test]
+    fn keys() {
+        let key = |token| to_json(token, true, Position::default()).unwrap();
+        assert_eq!(
+            key(Token::Identifier("a$_1".into())),
+            Token::String("a$_1".into())
+        );
+        assert_eq!(key(Token::Null), Token::String("null".into()));
+        assert_eq!(
+            key(Token::Number("NaN".into())),
+            Token::String("NaN".into())
+        );
+        assert_eq!(
+            key(Token::SingleQuoted("\"".into())),
+            Token::String("\\\"".into())
+        );
+    }
+}
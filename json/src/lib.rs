@@ -1,2 +1,29 @@
+mod canonical;
+mod diagnostic;
+mod duplicates;
+mod encoding;
+mod error;
+mod escape;
+mod filter;
+mod json5;
 pub mod minify;
-mod tokens;
+pub mod ndjson;
+mod number;
+mod options;
+mod path;
+mod pipeline;
+pub mod prettify;
+pub mod push;
+mod scan;
+#[cfg(feature = "serde")]
+pub mod ser;
+pub mod stream;
+pub mod tokens;
+mod validate;
+
+pub use crate::diagnostic::Diagnostic;
+pub use crate::duplicates::Duplicate;
+pub use crate::encoding::{decode, decode_lossy, Encoding};
+pub use crate::error::{Error, Limit, Position};
+pub use crate::options::{DuplicateKeys, Escapes, Limits, Options, Precision};
+pub use crate::tokens::{Lexer, Span, Token};
@@ -1,55 +1,208 @@
-use crate::tokens::{Lexer, Token};
+use crate::diagnostic::Diagnostic;
+use crate::duplicates::Duplicate;
+use crate::encoding::decode;
+use crate::error::{Error, Position};
+use crate::options::Options;
+use crate::pipeline::Pipeline;
+use crate::tokens::{Span, Token};
+use std::borrow::Cow;
 use std::fmt;
+use std::io;
 
 #[derive(Debug)]
-pub struct Minify {
-    minified: Vec<Token>,
+pub struct Minify<'a> {
+    minified: Vec<Token<'a>>,
+    rounded: usize,
+    duplicates: Vec<Duplicate>,
+    diagnostics: Vec<Diagnostic>,
 }
 
-impl Minify {
-    pub fn new(input: &str) -> Self {
-        let mut lexer = Lexer::new(input);
-        let minified = lexer.read_to_end();
+impl<'a> Minify<'a> {
+    pub fn new(input: &'a str) -> Result<Self, Error> {
+        Minify::with_options(input, Options::default())
+    }
 
-        Minify { minified }
+    pub fn with_options(input: &'a str, options: Options) -> Result<Self, Error> {
+        let mut pipeline = Pipeline::new(options);
+        let mut lexer = pipeline.lexer(input, Position::default());
+        let mut minified = Vec::new();
+        loop {
+            let token = lexer.next_token()?;
+            let end = token == Token::EndOfFile;
+            pipeline.push(token, lexer.start(), |token| {
+                minified.push(token);
+                Ok::<_, Error>(())
+            })?;
+            if end {
+                return Ok(Minify {
+                    minified,
+                    rounded: pipeline.rounded(),
+                    duplicates: pipeline.take_duplicates(),
+                    diagnostics: Vec::new(),
+                });
+            }
+        }
     }
-}
 
-impl fmt::Display for Minify {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let mut output = Vec::new();
+    /// Minifies JSON given as bytes in UTF-8, UTF-16 or UTF-32, with or
+    /// without a byte order mark, see `decode`. The output is always UTF-8
+    /// without a byte order mark.
+    pub fn from_bytes(input: &'a [u8], options: Options) -> Result<Self, Error> {
+        match decode(input)? {
+            Cow::Borrowed(input) => Minify::with_options(input, options),
+            Cow::Owned(input) => Minify::with_options(&input, options).map(Minify::into_owned),
+        }
+    }
+
+    /// Minifies input that may not be valid, fixing what it can instead of
+    /// failing at the first error.
+    ///
+    /// A token the lexer cannot read is skipped up to the next bracket,
+    /// comma, colon or line break, and replaced with `null` where a value is
+    /// needed. A token the grammar does not allow is fixed by inserting a
+    /// missing comma or colon, swapping a mismatched bracket or dropping it,
+    /// and brackets still open at the end are closed. Every fix is recorded
+    /// in `diagnostics`. Only exceeding one of the `Limits` and the errors
+    /// of options like `DuplicateKeys::Error` still fail.
+    pub fn recover(input: &'a str, options: Options) -> Result<Self, Error> {
+        let mut pipeline = Pipeline::new(options).recovering();
+        let mut lexer = pipeline.lexer(input, Position::default());
+        let mut minified = Vec::new();
+        let mut push = |token| {
+            minified.push(token);
+            Ok::<_, Error>(())
+        };
+        loop {
+            let token = match lexer.next_token() {
+                Ok(token) => token,
+                Err(error @ Error::LimitExceeded(..)) => return Err(error),
+                Err(error) => {
+                    let span = Span {
+                        start: lexer.start(),
+                        end: match error {
+                            // The comment runs to the end of the input
+                            Error::UnterminatedComment(_) => lexer.position(),
+                            _ => resynchronize(input, lexer.start()),
+                        },
+                    };
+                    pipeline.skipped(span, &error, &mut push)?;
+                    lexer = pipeline.lexer(&input[span.end.offset..], span.end);
+                    continue;
+                }
+            };
+            let end = token == Token::EndOfFile;
+            let span = Span {
+                start: lexer.start(),
+                end: lexer.position(),
+            };
+            pipeline.recover(token, span, &mut push)?;
+            if end {
+                return Ok(Minify {
+                    minified,
+                    rounded: pipeline.rounded(),
+                    duplicates: pipeline.take_duplicates(),
+                    diagnostics: pipeline.take_diagnostics(),
+                });
+            }
+        }
+    }
+
+    /// How many numbers were changed by rounding them to `Options::precision`.
+    pub fn rounded(&self) -> usize {
+        self.rounded
+    }
+
+    /// Every key that appeared more than once in the same object, unless
+    /// `Options::duplicate_keys` allows them.
+    pub fn duplicates(&self) -> &[Duplicate] {
+        &self.duplicates
+    }
+
+    /// The problems in the input that `recover` worked around, in the order
+    /// they were found.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Writes the minified output to `writer` without building it in memory
+    /// first. It is written in many small pieces, so `writer` should be
+    /// buffered.
+    pub fn write_to<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        self.write_fragments(|fragment| writer.write_all(fragment.as_bytes()))
+    }
+
+    /// Copies any text borrowed from the input.
+    fn into_owned(self) -> Minify<'static> {
+        Minify {
+            minified: self.minified.into_iter().map(Token::into_owned).collect(),
+            rounded: self.rounded,
+            duplicates: self.duplicates,
+            diagnostics: self.diagnostics,
+        }
+    }
+
+    /// Passes the output to `write` in the pieces it is stored in.
+    fn write_fragments<E, F>(&self, mut write: F) -> Result<(), E>
+    where
+        F: FnMut(&str) -> Result<(), E>,
+    {
         for token in &self.minified {
             match token {
                 Token::String(string) => {
-                    output.push("\"");
-                    output.push(string.as_str());
-                    output.push("\"");
+                    write("\"")?;
+                    write(string)?;
+                    write("\"")?;
                 }
-                Token::Number(string) => output.push(string.as_str()),
-                Token::Null => output.push("null"),
-                Token::True => output.push("true"),
-                Token::False => output.push("false"),
-                Token::Comma => output.push(","),
-                Token::Colon => output.push(":"),
-                Token::ObjectStart => output.push("{"),
-                Token::ObjectEnd => output.push("}"),
-                Token::ArrayStart => output.push("["),
-                Token::ArrayEnd => output.push("]"),
-                Token::EndOfFile => break,
-                Token::Error => {
-                    eprintln!("Parsing error!");
-                    break;
+                Token::SingleQuoted(string) => {
+                    write("'")?;
+                    write(string)?;
+                    write("'")?;
                 }
+                Token::Identifier(name) => write(name)?,
+                Token::Number(string) => write(string)?,
+                Token::Null => write("null")?,
+                Token::True => write("true")?,
+                Token::False => write("false")?,
+                Token::Comma => write(",")?,
+                Token::Colon => write(":")?,
+                Token::ObjectStart => write("{")?,
+                Token::ObjectEnd => write("}")?,
+                Token::ArrayStart => write("[")?,
+                Token::ArrayEnd => write("]")?,
+                Token::EndOfFile => break,
             }
         }
 
-        write!(f, "{}", output.join(""))
+        Ok(())
+    }
+}
+
+/// Where to continue lexing after a token that could not be read at
+/// `start`: the next character that can only be a token on its own, or the
+/// next line, since strings cannot span lines.
+fn resynchronize(input: &str, start: Position) -> Position {
+    let mut position = start;
+    for (i, c) in input[start.offset..].char_indices() {
+        if i > 0 && matches!(c, '{' | '}' | '[' | ']' | ',' | ':' | '\n') {
+            break;
+        }
+        position.advance(c);
+    }
+    position
+}
+
+impl fmt::Display for Minify<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.write_fragments(|fragment| f.write_str(fragment))
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
+    use crate::encoding::Encoding;
+    use crate::error::Limit;
+    use crate::options::{DuplicateKeys, Escapes, Limits, Precision};
 
     #[test]
     fn small() {
@@ -57,7 +210,7 @@ mod test {
   "a": "b",
   "c": "d"
 }"#;
-        let minified = Minify::new(input);
+        let minified = Minify::new(input).unwrap();
         assert_eq!(r#"{"a":"b","c":"d"}"#.to_owned(), format!("{}", minified));
     }
 
@@ -68,31 +221,867 @@ mod test {
   "age": 100,
   "messages": ["hello", "world", "!"]
 }"#;
-        let minified = Minify::new(input);
+        let minified = Minify::new(input).unwrap();
         assert_eq!(
             r#"{"name":"ola nordmann","age":100,"messages":["hello","world","!"]}"#,
             format!("{}", minified)
         );
     }
 
+    #[test]
+    fn write_to() {
+        let input = "{\"a\": [1, true, null], 'b': {c: \"d\"}}";
+        let options = Options {
+            json5: true,
+            keep_json5: true,
+            ..Options::default()
+        };
+        let minified = Minify::with_options(input, options).unwrap();
+        let mut output = Vec::new();
+        minified.write_to(&mut output).unwrap();
+        assert_eq!(output, b"{\"a\":[1,true,null],'b':{c:\"d\"}}");
+        assert_eq!(output, minified.to_string().as_bytes());
+
+        let mut full = [0; 8];
+        let error = minified.write_to(&mut full[..]).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::WriteZero);
+    }
+
+    #[test]
+    fn from_bytes() {
+        let utf16: Vec<u8> = "\u{feff}{\"é\": [1, 2]}"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let minified = Minify::from_bytes(&utf16, Options::default()).unwrap();
+        assert_eq!(minified.to_string(), "{\"é\":[1,2]}");
+
+        let utf8 = b"\xef\xbb\xbf [true]";
+        let minified = Minify::from_bytes(utf8, Options::default()).unwrap();
+        assert_eq!(minified.to_string(), "[true]");
+
+        assert_eq!(
+            Minify::from_bytes(&utf16[..utf16.len() - 1], Options::default()).unwrap_err(),
+            Error::InvalidEncoding(
+                Encoding::Utf16Le,
+                Position {
+                    line: 1,
+                    column: 13,
+                    offset: 13
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn escapes() {
+        let input = r#"{ "a\\": "\\", "b": "\"\\\"", "c": "\u00e9" }"#;
+        assert_eq!(
+            r#"{"a\\":"\\","b":"\"\\\"","c":"\u00e9"}"#,
+            format!("{}", Minify::new(input).unwrap())
+        );
+    }
+
+    #[test]
+    fn errors() {
+        let error = Minify::new("{\n  \"a\": tru\n}").unwrap_err();
+        assert_eq!(
+            error,
+            Error::UnexpectedCharacter(
+                '\n',
+                Position {
+                    line: 2,
+                    column: 11,
+                    offset: 12
+                }
+            )
+        );
+        assert_eq!(
+            "unexpected character '\\n' at line 2, column 11",
+            error.to_string()
+        );
+
+        let source = "{\"a\": 'b'}";
+        let error = Minify::new(source).unwrap_err();
+        assert_eq!(
+            diagnostics::Renderer::new().render("a.json", source, &error.report()),
+            "error: unexpected character '\\''
+ --> a.json:1:7
+  |
+1 | {\"a\": 'b'}
+  |       ^
+  |
+  = help: JSON strings use double quotes
+"
+        );
+    }
+
+    #[test]
+    fn grammar() {
+        for input in &["[null,]", r#"{"a" "b"}"#, "}}{{"] {
+            assert!(
+                matches!(Minify::new(input), Err(Error::UnexpectedToken(..))),
+                "{}",
+                input
+            );
+        }
+        assert!(matches!(Minify::new("[1"), Err(Error::UnexpectedEof(_))));
+
+        let lenient = Options {
+            lenient: true,
+            ..Options::default()
+        };
+        let minified = Minify::with_options("[ null , ]", lenient.clone()).unwrap();
+        assert_eq!("[null,]", format!("{}", minified));
+
+        // Keys are still known as keys after invalid input
+        let json5 = Options {
+            json5: true,
+            ..lenient
+        };
+        let input = "{null: 1, true: 2, a: 3} {b: [}, c: 4}";
+        let minified = Minify::with_options(input, json5).unwrap();
+        assert_eq!(
+            minified.to_string(),
+            r#"{"null":1,"true":2,"a":3}{"b":[},"c":4}"#
+        );
+    }
+
+    // https://json5.org/
+    #[test]
+    fn json5() {
+        let source = r#"// comments
+{
+  unquoted: 'and you can quote me on that',
+  singleQuotes: 'I can use "double quotes" here',
+  lineBreaks: "Look, Mom! \
+No \\n's!",
+  hexadecimal: 0xdecaf,
+  leadingDecimalPoint: .8675309, andTrailing: 8675309.,
+  positiveSign: +1,
+  trailingComma: 'in objects', andIn: ['arrays',],
+  "backwardsCompatible": "with JSON",
+}
+"#;
+        let options = Options {
+            json5: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            r#"{"unquoted":"and you can quote me on that","singleQuotes":"I can use \"double quotes\" here","lineBreaks":"Look, Mom! No \\n's!","hexadecimal":912559,"leadingDecimalPoint":0.8675309,"andTrailing":8675309,"positiveSign":1,"trailingComma":"in objects","andIn":["arrays"],"backwardsCompatible":"with JSON"}"#,
+            format!("{}", Minify::with_options(source, options.clone()).unwrap())
+        );
+
+        let keep = Options {
+            keep_json5: true,
+            ..options.clone()
+        };
+        assert_eq!(
+            "{unquoted:'and you can quote me on that',singleQuotes:'I can use \"double quotes\" here',lineBreaks:\"Look, Mom! \\\nNo \\\\n's!\",hexadecimal:0xdecaf,leadingDecimalPoint:.8675309,andTrailing:8675309.,positiveSign:+1,trailingComma:'in objects',andIn:['arrays'],\"backwardsCompatible\":\"with JSON\"}",
+            format!("{}", Minify::with_options(source, keep).unwrap())
+        );
+
+        let minify = |source| Minify::with_options(source, options.clone());
+        assert_eq!(
+            r#"{"null":null,"true":-31,"x":"\u0041\u000b"}"#,
+            format!(
+                "{}",
+                minify(r#"{null: null, true: -0x1f, x: '\x41\v'}"#).unwrap()
+            )
+        );
+        assert!(matches!(
+            minify("[1, -Infinity]"),
+            Err(Error::NonFiniteNumber(Position { column: 5, .. }))
+        ));
+        assert!(matches!(
+            Minify::new("{a: 1}"),
+            Err(Error::UnexpectedCharacter('a', _))
+        ));
+    }
+
+    #[test]
+    fn canonical() {
+        let canonical = |source| {
+            let options = Options {
+                canonical: true,
+                ..Options::default()
+            };
+            Minify::with_options(source, options).map(|minified| minified.to_string())
+        };
+
+        // The examples from RFC 8785
+        let source = r#"{
+  "numbers": [333333333.33333329, 1E30, 4.50, 2e-3, 0.000000000000000000000000001],
+  "string": "\u20ac$\u000F\u000aA'\u0042\u0022\u005c\\\"\/",
+  "literals": [null, true, false]
+}"#;
+        assert_eq!(
+            Ok(r#"{"literals":[null,true,false],"numbers":[333333333.3333333,1e+30,4.5,0.002,1e-27],"string":"€$\u000f\nA'B\"\\\\\"/"}"#.to_string()),
+            canonical(source)
+        );
+        let source = r#"{
+  "\u20ac": "Euro Sign",
+  "\r": "Carriage Return",
+  "\ufb33": "Hebrew Letter Dalet With Dagesh",
+  "1": "One",
+  "\ud83d\ude00": "Emoji: Grinning Face",
+  "\u0080": "Control",
+  "\u00f6": "Latin Small Letter O With Diaeresis"
+}"#;
+        assert_eq!(
+            Ok("{\"\\r\":\"Carriage Return\",\"1\":\"One\",\"\u{80}\":\"Control\",\"ö\":\"Latin Small Letter O With Diaeresis\",\"€\":\"Euro Sign\",\"😀\":\"Emoji: Grinning Face\",\"\u{fb33}\":\"Hebrew Letter Dalet With Dagesh\"}".to_string()),
+            canonical(source)
+        );
+
+        assert_eq!(
+            canonical(r#"{"b": [{"d": 1, "c": -0}], "a": {}}"#),
+            canonical(r#"{"a":{},"b":[{"c":0.0,"d":1e0}]}"#)
+        );
+        assert_eq!(
+            Err(Error::NumberOutOfRange(Position {
+                line: 1,
+                column: 2,
+                offset: 1
+            })),
+            canonical("[1e400]")
+        );
+        assert!(matches!(
+            canonical("[1,]"),
+            Err(Error::UnexpectedToken("']'", _))
+        ));
+
+        // I-JSON has no duplicate keys, unless they are resolved first
+        assert_eq!(
+            canonical(r#"{"b": 1, "\u0061": 1, "a": 2}"#),
+            Err(Error::DuplicateKey(
+                "a".to_string(),
+                Position {
+                    line: 1,
+                    column: 23,
+                    offset: 22
+                }
+            ))
+        );
+        let last = Options {
+            canonical: true,
+            duplicate_keys: DuplicateKeys::KeepLast,
+            ..Options::default()
+        };
+        assert_eq!(
+            Minify::with_options(r#"{"b": 1, "a": 1, "a": 2}"#, last)
+                .unwrap()
+                .to_string(),
+            r#"{"a":2,"b":1}"#
+        );
+
+        // Options that would change the canonical form fail
+        for (escapes, html_safe) in [(Escapes::Ascii, false), (Escapes::Keep, true)] {
+            let options = Options {
+                canonical: true,
+                escapes,
+                html_safe,
+                ..Options::default()
+            };
+            assert!(matches!(
+                Minify::with_options("[]", options),
+                Err(Error::ConflictingOptions("canonical output", _))
+            ));
+        }
+        let decode = Options {
+            canonical: true,
+            escapes: Escapes::Decode,
+            ..Options::default()
+        };
+        assert!(Minify::with_options("[]", decode).is_ok());
+    }
+
+    #[test]
+    fn numbers() {
+        let source = "[1.0, 1.50, 1e+03, 0.000001, -0, -0.0, 12345678901234567890123456789.0]";
+        assert_eq!(
+            "[1.0,1.50,1e+03,0.000001,-0,-0.0,12345678901234567890123456789.0]",
+            format!("{}", Minify::new(source).unwrap())
+        );
+
+        let options = Options {
+            normalize_numbers: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            "[1,1.5,1e3,1e-6,-0,-0,12345678901234567890123456789]",
+            format!("{}", Minify::with_options(source, options.clone()).unwrap())
+        );
+        let zero = Options {
+            normalize_negative_zero: true,
+            ..options.clone()
+        };
+        assert_eq!(
+            "[1,1.5,1e3,1e-6,0,0,12345678901234567890123456789]",
+            format!("{}", Minify::with_options(source, zero).unwrap())
+        );
+
+        let json5 = Options {
+            json5: true,
+            ..options
+        };
+        assert_eq!(
+            r#"{"a":5,"b":[0.5,1e3]}"#,
+            format!(
+                "{}",
+                Minify::with_options("{a: 5., b: [.50, +0x3E8]}", json5).unwrap()
+            )
+        );
+        assert!(matches!(
+            Minify::new("[1.2.3]"),
+            Err(Error::InvalidNumber(Position { column: 2, .. }))
+        ));
+    }
+
+    #[test]
+    fn precision() {
+        let source = r#"{
+  "type": "Feature",
+  "id": 1234567,
+  "geometry": {"type": "Point", "coordinates": [10.123456789012345, -73.98571, 12.5]},
+  "properties": {"accuracy": 0.123456789}
+}"#;
+        let options = Options {
+            precision: Some(Precision::DecimalPlaces(6)),
+            ..Options::default()
+        };
+        let minified = Minify::with_options(source, options.clone()).unwrap();
+        assert_eq!(
+            r#"{"type":"Feature","id":1234567,"geometry":{"type":"Point","coordinates":[10.123457,-73.98571,12.5]},"properties":{"accuracy":0.123457}}"#,
+            format!("{}", minified)
+        );
+        assert_eq!(2, minified.rounded());
+
+        let options = Options {
+            precision: Some(Precision::SignificantDigits(3)),
+            precision_paths: vec!["/geometry/coordinates".to_string()],
+            ..options
+        };
+        let minified = Minify::with_options(source, options).unwrap();
+        assert_eq!(
+            r#"{"type":"Feature","id":1234567,"geometry":{"type":"Point","coordinates":[10.1,-74,12.5]},"properties":{"accuracy":0.123456789}}"#,
+            format!("{}", minified)
+        );
+        assert_eq!(2, minified.rounded());
+
+        let options = Options {
+            json5: true,
+            precision: Some(Precision::DecimalPlaces(1)),
+            precision_paths: vec!["/*/1".to_string()],
+            ..Options::default()
+        };
+        let minified =
+            Minify::with_options("{a: [1.25, 2.25], 'b': [.25, +3.25,]}", options).unwrap();
+        assert_eq!(
+            r#"{"a":[1.25,2.3],"b":[0.25,3.3]}"#,
+            format!("{}", minified)
+        );
+        assert_eq!(2, minified.rounded());
+    }
+
+    #[test]
+    fn escape_options() {
+        let source = r#"{"caf\u00e9":["\u00e9t\u00e9 \/ \ud83d\ude00","na\u00efve \"","日本"]}"#;
+        assert_eq!(source, format!("{}", Minify::new(source).unwrap()));
+
+        let decode = Options {
+            escapes: Escapes::Decode,
+            ..Options::default()
+        };
+        assert_eq!(
+            r#"{"café":["été / 😀","naïve \"","日本"]}"#,
+            format!("{}", Minify::with_options(source, decode).unwrap())
+        );
+
+        let ascii = Options {
+            escapes: Escapes::Ascii,
+            ..Options::default()
+        };
+        assert_eq!(
+            r#"{"caf\u00e9":["\u00e9t\u00e9 \/ \ud83d\ude00","na\u00efve \"","\u65e5\u672c"]}"#,
+            format!("{}", Minify::with_options(source, ascii.clone()).unwrap())
+        );
+
+        let decoded = r#"{"café":["été / 😀"]}"#;
+        assert_eq!(
+            r#"{"caf\u00e9":["\u00e9t\u00e9 / \ud83d\ude00"]}"#,
+            format!("{}", Minify::with_options(decoded, ascii.clone()).unwrap())
+        );
+
+        let json5 = Options {
+            json5: true,
+            keep_json5: true,
+            ..ascii
+        };
+        assert_eq!(
+            r#"{cl\u00e9:'\ud83d\ude00'}"#,
+            format!("{}", Minify::with_options("{clé: '😀'}", json5).unwrap())
+        );
+
+        // Only the quote of the string itself stays escaped
+        let source = r#"{a: '\u00e9 \' \" \x41', "\'": "\" \' \v"}"#;
+        let keep = Options {
+            json5: true,
+            keep_json5: true,
+            escapes: Escapes::Decode,
+            ..Options::default()
+        };
+        assert_eq!(
+            r#"{a:'é \' " A',"'":"\" ' \u000b"}"#,
+            format!("{}", Minify::with_options(source, keep.clone()).unwrap())
+        );
+        let convert = Options {
+            keep_json5: false,
+            ..keep
+        };
+        assert_eq!(
+            r#"{"a":"é ' \" A","'":"\" ' \u000b"}"#,
+            format!("{}", Minify::with_options(source, convert).unwrap())
+        );
+    }
+
+    #[test]
+    fn html_safe() {
+        let options = Options {
+            html_safe: true,
+            ..Options::default()
+        };
+        let source = "{\"html\": \"</script><!-- & -->\", \"js\": \"\u{2028}\u{2029}\", \"<\": 1}";
+        assert_eq!(
+            r#"{"html":"\u003c/script\u003e\u003c!-- \u0026 --\u003e","js":"\u2028\u2029","\u003c":1}"#,
+            format!("{}", Minify::with_options(source, options.clone()).unwrap())
+        );
+
+        // Decoded escapes are escaped again
+        let decode = Options {
+            escapes: Escapes::Decode,
+            ..options
+        };
+        assert_eq!(
+            r#"["\u003c/script\u003e"]"#,
+            format!(
+                "{}",
+                Minify::with_options(r#"["<\/script>"]"#, decode).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        let source = "{\"a\": 1, \"b\": 2,\n \"\\u0061\": [3], \"c\": {\"b\": 4}}";
+        let with = |duplicate_keys| Options {
+            duplicate_keys,
+            ..Options::default()
+        };
+
+        let allowed = Minify::with_options(source, Options::default()).unwrap();
+        assert!(allowed.duplicates().is_empty());
+
+        let first = Minify::with_options(source, with(DuplicateKeys::KeepFirst)).unwrap();
+        assert_eq!(r#"{"a":1,"b":2,"c":{"b":4}}"#, format!("{}", first));
+        let duplicate = &first.duplicates()[0];
+        assert_eq!(duplicate.key, "a");
+        assert_eq!((duplicate.first.line, duplicate.first.column), (1, 2));
+        assert_eq!(
+            (duplicate.duplicate.line, duplicate.duplicate.column),
+            (2, 2)
+        );
+
+        let last = Minify::with_options(source, with(DuplicateKeys::KeepLast)).unwrap();
+        assert_eq!(r#"{"b":2,"\u0061":[3],"c":{"b":4}}"#, format!("{}", last));
+        assert_eq!(first.duplicates(), last.duplicates());
+
+        assert_eq!(
+            Minify::with_options(source, with(DuplicateKeys::Error)).unwrap_err(),
+            Error::DuplicateKey("a".to_string(), duplicate.duplicate)
+        );
+
+        // JSON5 keys are compared by their names
+        let json5 = Options {
+            json5: true,
+            keep_json5: true,
+            ..with(DuplicateKeys::KeepLast)
+        };
+        assert_eq!(
+            "{b:2,'a':3}",
+            format!(
+                "{}",
+                Minify::with_options("{a: 1, b: 2, 'a': 3,}", json5).unwrap()
+            )
+        );
+        // Also with escapes only JSON5 has
+        let error = Minify::with_options(
+            r"{a: 1, '\x61': 2, 'b\
+': 3, b: 4}",
+            Options {
+                json5: true,
+                keep_json5: true,
+                ..with(DuplicateKeys::Error)
+            },
+        )
+        .unwrap_err();
+        assert!(
+            matches!(&error, Error::DuplicateKey(key, position) if key == "a" && position.offset == 7)
+        );
+        let filtered = Options {
+            json5: true,
+            keep_json5: true,
+            drop_keys: vec!["a".to_string()],
+            ..Options::default()
+        };
+        assert_eq!(
+            "{b:1}",
+            Minify::with_options(r"{'\x61': 2, b: 1, \u0061: 3}", filtered)
+                .unwrap()
+                .to_string()
+        );
+
+        // Duplicates are resolved before members are dropped
+        let filtered = |source, duplicate_keys| {
+            let options = Options {
+                drop_nulls: true,
+                ..with(duplicate_keys)
+            };
+            Minify::with_options(source, options).unwrap().to_string()
+        };
+        let source = r#"{"a": 1, "b": 2, "a": null}"#;
+        assert_eq!(filtered(source, DuplicateKeys::KeepLast), r#"{"b":2}"#);
+        assert_eq!(
+            filtered(source, DuplicateKeys::KeepFirst),
+            r#"{"a":1,"b":2}"#
+        );
+        let source = r#"{"a": null, "a": 1}"#;
+        assert_eq!(filtered(source, DuplicateKeys::KeepFirst), "{}");
+        assert_eq!(filtered(source, DuplicateKeys::KeepLast), r#"{"a":1}"#);
+    }
+
+    #[test]
+    fn recover() {
+        let recover = |source| {
+            let minified = Minify::recover(source, Options::default()).unwrap();
+            let found = minified
+                .diagnostics()
+                .iter()
+                .map(|diagnostic| {
+                    let suggestion = diagnostic.suggestion.as_deref().unwrap_or("");
+                    let start = diagnostic.span.start.column;
+                    (start, diagnostic.message.clone(), suggestion.to_string())
+                })
+                .collect::<Vec<_>>();
+            (minified.to_string(), found)
+        };
+        let found = |column, message: &str, suggestion: &str| {
+            (column, message.to_string(), suggestion.to_string())
+        };
+
+        let valid = r#"{"a": [1, 2], "b": null}"#;
+        assert_eq!(recover(valid), (r#"{"a":[1,2],"b":null}"#.into(), vec![]));
+
+        assert_eq!(
+            recover(r#"{"a": 1 "b" 2, "c": [1, 2,],}"#),
+            (
+                r#"{"a":1,"b":2,"c":[1,2]}"#.into(),
+                vec![
+                    found(9, "missing ','", "insert ','"),
+                    found(13, "missing ':'", "insert ':'"),
+                    found(26, "trailing comma", "remove the ','"),
+                    found(28, "trailing comma", "remove the ','"),
+                ]
+            )
+        );
+        assert_eq!(
+            recover(r#"{"a": tru, "b": [1, ?, 3}"#),
+            (
+                r#"{"a":null,"b":[1,null,3]}"#.into(),
+                vec![
+                    found(7, "unexpected character ','", "replace it with a value"),
+                    found(21, "unexpected character '?'", "replace it with a value"),
+                    found(25, "mismatched '}'", "replace it with ']'"),
+                    found(26, "unexpected end of input", "insert '}'"),
+                ]
+            )
+        );
+        assert_eq!(
+            recover("[1, : 2]]\n{\"a\""),
+            (
+                "[1,2]".into(),
+                vec![
+                    found(5, "unexpected ':'", "remove it"),
+                    found(9, "unexpected ']'", "remove it"),
+                    found(1, "unexpected '{'", "remove it"),
+                    found(2, "unexpected string", "remove it"),
+                ]
+            )
+        );
+        assert_eq!(
+            recover("{\"a\": [\"b"),
+            (
+                r#"{"a":[null]}"#.into(),
+                vec![
+                    found(8, "unterminated string", "replace it with a value"),
+                    found(10, "unexpected end of input", "insert ']}'"),
+                ]
+            )
+        );
+
+        let limited = Options {
+            limits: Limits {
+                max_depth: Some(1),
+                ..Limits::default()
+            },
+            ..Options::default()
+        };
+        assert!(matches!(
+            Minify::recover("[[1 2]]", limited),
+            Err(Error::LimitExceeded(Limit::Depth, _))
+        ));
+    }
+
+    #[test]
+    fn filters() {
+        let source = r#"{
+            "id": 1,
+            "name": null,
+            "tags": [],
+            "meta": {"note": null, "extra": {}},
+            "items": [
+                {"value": null, "debug": {"trace": [1, 2]}, "list": [null, {}]},
+                {"debug": true, "value": 2, "nested": {"debug": 3}}
+            ],
+            "debug": false
+        }"#;
+        let minify = |options| format!("{}", Minify::with_options(source, options).unwrap());
+
+        let nulls = Options {
+            drop_nulls: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            r#"{"id":1,"tags":[],"meta":{"extra":{}},"items":[{"debug":{"trace":[1,2]},"list":[null,{}]},{"debug":true,"value":2,"nested":{"debug":3}}],"debug":false}"#,
+            minify(nulls.clone())
+        );
+
+        let empty = Options {
+            drop_empty: true,
+            ..nulls
+        };
+        assert_eq!(
+            r#"{"id":1,"items":[{"debug":{"trace":[1,2]},"list":[null,{}]},{"debug":true,"value":2,"nested":{"debug":3}}],"debug":false}"#,
+            minify(empty.clone())
+        );
+
+        let keys = Options {
+            drop_keys: vec!["debug".to_string()],
+            ..empty.clone()
+        };
+        assert_eq!(
+            r#"{"id":1,"items":[{"list":[null,{}]},{"value":2}]}"#,
+            minify(keys)
+        );
+
+        // Locations refer to the input, before anything is dropped
+        let paths = Options {
+            drop_paths: vec!["/items/*/debug".to_string(), "/id".to_string()],
+            ..empty
+        };
+        assert_eq!(
+            r#"{"items":[{"list":[null,{}]},{"value":2,"nested":{"debug":3}}],"debug":false}"#,
+            minify(paths)
+        );
+
+        // Locations to round numbers at refer to the input too
+        let rounded = Options {
+            drop_keys: vec!["drop".to_string()],
+            precision: Some(Precision::DecimalPlaces(1)),
+            precision_paths: vec!["/keep/1".to_string(), "/nested/b".to_string()],
+            ..Options::default()
+        };
+        assert_eq!(
+            Minify::with_options(
+                r#"{"drop": 1.25, "keep": [1.25, 2.25], "nested": {"a": 1.25, "b": 2.25}}"#,
+                rounded
+            )
+            .unwrap()
+            .to_string(),
+            r#"{"keep":[1.25,2.3],"nested":{"a":1.25,"b":2.3}}"#
+        );
+
+        // Everything dropped still leaves the document
+        let options = Options {
+            drop_nulls: true,
+            drop_empty: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            "{}",
+            format!(
+                "{}",
+                Minify::with_options(r#"{"a": {"b": [], "c": null}}"#, options).unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn limits() {
+        let source = r#"{"a": [[1, "four"]], "bc": 12.5}"#;
+        let limited = |limits| {
+            Minify::with_options(
+                source,
+                Options {
+                    limits,
+                    ..Options::default()
+                },
+            )
+            .map(|minified| minified.to_string())
+            .map_err(|error| (error.to_string(), error.position().column))
+        };
+        let exceeded = |limit: Limit, column| {
+            Err((
+                format!("{} limit exceeded at line 1, column {}", limit, column),
+                column,
+            ))
+        };
+
+        let at_limit = Limits {
+            max_depth: Some(3),
+            max_string_bytes: Some(4),
+            max_number_length: Some(4),
+            max_tokens: Some(15),
+            max_input_bytes: Some(source.len()),
+        };
+        assert_eq!(
+            limited(at_limit),
+            Ok(r#"{"a":[[1,"four"]],"bc":12.5}"#.to_string())
+        );
+
+        let cases = [
+            (
+                Limits {
+                    max_depth: Some(2),
+                    ..at_limit
+                },
+                Limit::Depth,
+                8,
+            ),
+            (
+                Limits {
+                    max_string_bytes: Some(3),
+                    ..at_limit
+                },
+                Limit::StringBytes,
+                12,
+            ),
+            (
+                Limits {
+                    max_number_length: Some(3),
+                    ..at_limit
+                },
+                Limit::NumberLength,
+                28,
+            ),
+            (
+                Limits {
+                    max_tokens: Some(14),
+                    ..at_limit
+                },
+                Limit::Tokens,
+                32,
+            ),
+            (
+                Limits {
+                    max_input_bytes: Some(20),
+                    ..at_limit
+                },
+                Limit::InputBytes,
+                22,
+            ),
+        ];
+        for &(limits, limit, column) in &cases {
+            assert_eq!(limited(limits), exceeded(limit, column));
+        }
+    }
+
+    fn minify_jsonc(source: &str) -> String {
+        let options = Options {
+            comments: true,
+            ..Options::default()
+        };
+        format!("{}", Minify::with_options(source, options).unwrap())
+    }
+
     // https://github.com/getify/JSON.minify/blob/javascript/tests.js
     #[test]
     fn json_minify_js_1() {
-        let source = "\
+        let source = "// this is a JSON file with comments\n\
 			{\n\
-				\"foo\": \"bar\",	\n\
+				\"foo\": \"bar\",	// this is cool\n\
 				\"bar\": [\n\
 					\"baz\", \"bum\", \"zam\"\n\
 				],\n\
-                \n
+			/* the rest of this document is just fluff\n\
+			   in case you are interested. */\n\
 				\"something\": 10,\n\
 				\"else\": 20\n\
 			}\n\
 			\n\
+			/* NOTE: You can easily strip the whitespace and comments \n\
+			   from such a file with the JSON.minify() project hosted \n\
+			   here on github at http://github.com/getify/JSON.minify \n\
 			*/\n";
         assert_eq!(
             "{\"foo\":\"bar\",\"bar\":[\"baz\",\"bum\",\"zam\"],\"something\":10,\"else\":20}",
-            format!("{}", Minify::new(source))
+            minify_jsonc(source)
+        );
+        // Comments are still rejected unless they are enabled
+        assert!(matches!(
+            Minify::new(source),
+            Err(Error::UnexpectedCharacter('/', _))
+        ));
+    }
+
+    #[test]
+    fn json_minify_js_2() {
+        let source = "\n\
+			{\"/*\":\"*/\",\"//\":\"\",/*\"//\"*/\"/*/\"://\n\
+			\"//\"}\n\
+			\n";
+        assert_eq!(
+            "{\"/*\":\"*/\",\"//\":\"\",\"/*/\":\"//\"}",
+            minify_jsonc(source)
+        );
+    }
+
+    #[test]
+    fn json_minify_js_3() {
+        let source = "/*\n\
+			this is a \n\
+			multi line comment */{\n\
+			\n\
+			\"foo\"\n\
+			:\n\
+				\"bar/*\"// something\n\
+				,	\"b\\\"az\":/*\n\
+			something else */\"blah\"\n\
+			\n\
+			}\n";
+        assert_eq!(
+            "{\"foo\":\"bar/*\",\"b\\\"az\":\"blah\"}",
+            minify_jsonc(source)
+        );
+    }
+
+    #[test]
+    fn json_minify_js_4() {
+        let source = "{\"foo\": \"ba\\\"r//\", \"bar\\\\\": \"b\\\\\\\"a/*z\", \n\
+			\"baz\\\\\\\\\": /* yay */ \"fo\\\\\\\\\\\"*/o\" \n\
+			}\n";
+        assert_eq!(
+            "{\"foo\":\"ba\\\"r//\",\"bar\\\\\":\"b\\\\\\\"a/*z\",\"baz\\\\\\\\\":\"fo\\\\\\\\\\\"*/o\"}",
+            minify_jsonc(source)
         );
     }
 }
@@ -0,0 +1,251 @@
+//! Newline-delimited JSON (NDJSON, also known as JSON Lines), where every
+//! line holds one document.
+
+use crate::encoding::Encoding;
+use crate::error::{Error, Limit, Position};
+use crate::options::Options;
+use crate::pipeline::Pipeline;
+use crate::tokens::{self, Token};
+use std::fmt::Write as _;
+use std::io::{self, BufRead, Read, Write};
+use std::str;
+
+/// Minifies newline-delimited JSON from a reader into a writer, one record
+/// at a time.
+///
+/// Every line is minified on its own and written on a line of its own. A
+/// record that fails to minify is left out of the output and its error is
+/// returned once the whole input has been read, so one bad record does not
+/// lose the rest. Lines that are blank are skipped.
+pub struct Minifier<R, W> {
+    reader: R,
+    writer: W,
+    options: Options,
+}
+
+impl<R: BufRead, W: Write> Minifier<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Minifier {
+            reader,
+            writer,
+            options: Options::default(),
+        }
+    }
+
+    pub fn options(mut self, options: Options) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Minifies every record and returns the writer, along with the errors
+    /// of the records that were left out. Their positions are in the whole
+    /// input, so the line of the error is the line of the record. A line
+    /// that is not valid UTF-8 is left out with an `Error::InvalidEncoding`.
+    ///
+    /// Every line is read as a whole before it is minified. With
+    /// `Limits::max_input_bytes` no more than that is read, and the input
+    /// stops at the line that goes over it with an `Error::LimitExceeded`.
+    pub fn minify(mut self) -> io::Result<(W, Vec<Error>)> {
+        let mut errors = Vec::new();
+        let mut line = Vec::new();
+        let mut output = String::new();
+        let mut start = Position::default();
+        let limit = self.options.limits.max_input_bytes;
+        let mut pipeline = Pipeline::new(self.options);
+        loop {
+            line.clear();
+            let read = match limit {
+                Some(limit) => {
+                    // Up to the first byte past the limit
+                    let room = limit.saturating_sub(start.offset) as u64 + 1;
+                    (&mut self.reader).take(room).read_until(b'\n', &mut line)?
+                }
+                None => self.reader.read_until(b'\n', &mut line)?,
+            };
+            if read == 0 {
+                break;
+            }
+            let content = line.strip_suffix(b"\n").unwrap_or(&line);
+            if tokens::exceeds(limit, start.offset + content.len()) {
+                errors.push(Error::LimitExceeded(Limit::InputBytes, start));
+                break;
+            }
+
+            match str::from_utf8(&line) {
+                Ok(record) => {
+                    // Keep errors at the end of the record on its own line
+                    let record = record.trim_end_matches(['\n', '\r']);
+                    if !record.trim().is_empty() {
+                        output.clear();
+                        pipeline.reset();
+                        match minify_record(record, start, &mut pipeline, &mut output) {
+                            Ok(()) => {
+                                self.writer.write_all(output.as_bytes())?;
+                                self.writer.write_all(b"\n")?;
+                            }
+                            Err(error) => errors.push(error),
+                        }
+                    }
+                }
+                Err(error) => {
+                    let mut position = start;
+                    let valid = str::from_utf8(&line[..error.valid_up_to()]).unwrap();
+                    valid.chars().for_each(|c| position.advance(c));
+                    errors.push(Error::InvalidEncoding(Encoding::Utf8, position));
+                }
+            }
+
+            start.line += 1;
+            start.offset += line.len();
+        }
+
+        self.writer.flush()?;
+        Ok((self.writer, errors))
+    }
+}
+
+/// Minifies a single record, which starts at `start` in the input, into
+/// `output`.
+fn minify_record(
+    record: &str,
+    start: Position,
+    pipeline: &mut Pipeline,
+    output: &mut String,
+) -> Result<(), Error> {
+    let mut lexer = pipeline.lexer(record, start);
+    loop {
+        let token = lexer.next_token()?;
+        let end = token == Token::EndOfFile;
+        pipeline.push(token, lexer.start(), |token| {
+            write!(output, "{}", token).unwrap();
+            Ok::<_, Error>(())
+        })?;
+        if end {
+            return Ok(());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::options::Limits;
+
+    #[test]
+    fn records() {
+        let input =
+            "{\"a\": 1,\n\"b\": 2}\n\n  [1, 2] \r\n\"text\"\n{\"a\": tru}\n1 2\n{ \"c\" : null }";
+        let (output, errors) = Minifier::new(input.as_bytes(), Vec::new())
+            .minify()
+            .unwrap();
+        assert_eq!(
+            "[1,2]\n\"text\"\n{\"c\":null}\n",
+            String::from_utf8(output).unwrap()
+        );
+
+        let lines: Vec<_> = errors.iter().map(|error| error.position().line).collect();
+        assert_eq!(lines, [1, 2, 6, 7]);
+        assert_eq!(
+            errors[2],
+            Error::UnexpectedCharacter('}', errors[2].position())
+        );
+        assert_eq!(errors[3].position().column, 3);
+    }
+
+    #[test]
+    fn options() {
+        let input = "{\"a\": 1.50, \"b\": null}\n{\"a\": 1, \"a\": 2}\n";
+        let options = Options {
+            normalize_numbers: true,
+            drop_nulls: true,
+            duplicate_keys: crate::options::DuplicateKeys::Error,
+            ..Options::default()
+        };
+        let (output, errors) = Minifier::new(input.as_bytes(), Vec::new())
+            .options(options)
+            .minify()
+            .unwrap();
+        assert_eq!(b"{\"a\":1.5}\n", output.as_slice());
+        assert!(matches!(&errors[..], [Error::DuplicateKey(key, _)] if key == "a"));
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let input = b"{\"a\":1}\n[\"\xff\"]\n{\"b\": [2,\n{\"c\": 3}\n";
+        let (output, errors) = Minifier::new(&input[..], Vec::new()).minify().unwrap();
+        assert_eq!(output, b"{\"a\":1}\n{\"c\":3}\n");
+        assert_eq!(
+            errors[0],
+            Error::InvalidEncoding(
+                Encoding::Utf8,
+                Position {
+                    line: 2,
+                    column: 3,
+                    offset: 10
+                }
+            )
+        );
+        // The pipeline starts over after a record that ends early
+        assert!(matches!(errors[1], Error::UnexpectedEof(_)));
+        assert_eq!(errors.len(), 2);
+    }
+
+    /// Gives out `bytes` and then fails, to show when a reader is read past
+    /// a point.
+    struct Until<'a>(&'a [u8]);
+
+    impl Read for Until<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            if self.0.is_empty() {
+                return Err(io::ErrorKind::Other.into());
+            }
+            let length = self.0.len().min(buf.len());
+            buf[..length].copy_from_slice(&self.0[..length]);
+            self.0 = &self.0[length..];
+            Ok(length)
+        }
+    }
+
+    #[test]
+    fn input_limit() {
+        let options = Options {
+            limits: Limits {
+                max_input_bytes: Some(16),
+                ..Limits::default()
+            },
+            ..Options::default()
+        };
+        let minify = |input: &[u8]| {
+            let reader = io::BufReader::with_capacity(4, input);
+            Minifier::new(reader, Vec::new())
+                .options(options.clone())
+                .minify()
+        };
+
+        // Lines that end right at the limit
+        let (output, errors) = minify(b"[1, 2]\n[3, 4, 5]\n").unwrap();
+        assert_eq!(output, b"[1,2]\n[3,4,5]\n");
+        assert!(errors.is_empty());
+
+        // A line that never ends is only read up to the limit
+        let mut input = b"[1]\n[\"".to_vec();
+        input.extend(vec![b'a'; 13]);
+        let reader = io::BufReader::with_capacity(4, Until(&input));
+        let (output, errors) = Minifier::new(reader, Vec::new())
+            .options(options)
+            .minify()
+            .unwrap();
+        assert_eq!(output, b"[1]\n");
+        assert_eq!(
+            errors,
+            [Error::LimitExceeded(
+                Limit::InputBytes,
+                Position {
+                    line: 2,
+                    column: 1,
+                    offset: 4
+                }
+            )]
+        );
+    }
+}
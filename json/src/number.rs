@@ -0,0 +1,260 @@
+use crate::options::Precision;
+use std::borrow::Cow;
+use std::convert::TryFrom;
+
+/// A decimal number as `digits * 10^exponent`, without leading or trailing
+/// zeros in `digits`, so zero has no digits.
+struct Decimal {
+    negative: bool,
+    digits: String,
+    exponent: i64,
+}
+
+impl Decimal {
+    /// Splits up a number that follows the JSON grammar, or returns `None`
+    /// if the exponent is unusually large.
+    fn parse(number: &str) -> Option<Self> {
+        let (negative, unsigned) = match number.strip_prefix('-') {
+            Some(unsigned) => (true, unsigned),
+            None => (false, number),
+        };
+        let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+            Some(e) => (&unsigned[..e], &unsigned[e + 1..]),
+            None => (unsigned, "0"),
+        };
+        let (integer, fraction) = match mantissa.find('.') {
+            Some(dot) => (&mantissa[..dot], &mantissa[dot + 1..]),
+            None => (mantissa, ""),
+        };
+        let exponent = match exponent.trim_start_matches('+').parse::<i64>() {
+            Ok(exponent) if exponent.abs() < 1 << 48 => exponent,
+            _ => return None,
+        };
+
+        let all = format!("{}{}", integer, fraction);
+        let significant = all.trim_end_matches('0');
+        let exponent = exponent - fraction.len() as i64 + (all.len() - significant.len()) as i64;
+        Some(Decimal {
+            negative,
+            digits: significant.trim_start_matches('0').to_string(),
+            exponent,
+        })
+    }
+
+    /// Writes the number in its shortest form, see `shortest_form`.
+    fn to_shortest(&self, negative_zero: bool) -> String {
+        let mut shortest = String::new();
+        if self.negative && (negative_zero || !self.digits.is_empty()) {
+            shortest.push('-');
+        }
+        if self.digits.is_empty() {
+            shortest.push('0');
+        } else {
+            shortest.push_str(&shortest_form(&self.digits, self.exponent));
+        }
+
+        shortest
+    }
+
+    /// Rounds half away from zero to `keep` digits, and returns whether the
+    /// value changed.
+    fn round(&mut self, keep: i64) -> bool {
+        let len = self.digits.len() as i64;
+        if keep >= len {
+            return false;
+        }
+        if keep < 0 {
+            self.digits.clear();
+            return true;
+        }
+
+        let up = self.digits.as_bytes()[keep as usize] >= b'5';
+        self.digits.truncate(keep as usize);
+        self.exponent += len - keep;
+        if up {
+            // Add one to the last digit, carrying past any nines
+            let nines = self.digits.len() - self.digits.trim_end_matches('9').len();
+            self.digits.truncate(self.digits.len() - nines);
+            self.exponent += nines as i64;
+            match self.digits.pop() {
+                Some(digit) => self.digits.push((digit as u8 + 1) as char),
+                None => self.digits.push('1'),
+            }
+        }
+        let significant = self.digits.trim_end_matches('0').len();
+        self.exponent += (self.digits.len() - significant) as i64;
+        self.digits.truncate(significant);
+
+        true
+    }
+}
+
+/// Rewrites a number that follows the JSON grammar in its shortest form with
+/// the same decimal value, like `1.50` as `1.5` or `0.000001` as `1e-6`. This
+/// works on the digits as written, so no precision is lost on numbers that
+/// do not fit in a double. Negative zero is written as `0` if
+/// `negative_zero` is false.
+pub(crate) fn normalize(number: Cow<str>, negative_zero: bool) -> Cow<str> {
+    // Exponents this large are unusual enough to leave alone
+    let shortest = match Decimal::parse(&number) {
+        Some(decimal) => decimal.to_shortest(negative_zero),
+        None => return number,
+    };
+
+    if shortest == *number {
+        number
+    } else {
+        shortest.into()
+    }
+}
+
+/// Rounds a number that follows the JSON grammar to `precision`, and writes
+/// it in its shortest form if that changed its value.
+pub(crate) fn round(number: &str, precision: Precision) -> Option<String> {
+    let mut decimal = Decimal::parse(number)?;
+    // Precisions past what an `i64` holds keep every digit
+    let keep = match precision {
+        Precision::SignificantDigits(digits) => i64::try_from(digits.max(1)).unwrap_or(i64::MAX),
+        // The last digit kept is the one for `10^-places`
+        Precision::DecimalPlaces(places) => (decimal.digits.len() as i64 + decimal.exponent)
+            .saturating_add(i64::try_from(places).unwrap_or(i64::MAX)),
+    };
+    if !decimal.round(keep) {
+        return None;
+    }
+
+    Some(decimal.to_shortest(false))
+}
+
+/// The shortest way to write `digits * 10^exponent`, preferring to not use
+/// an exponent when there is a tie.
+fn shortest_form(digits: &str, exponent: i64) -> String {
+    let k = digits.len() as i64;
+    // Writing out the zeros is never shorter once there are more of them than
+    // it takes to write the exponent, so there is no need to try
+    let mut candidates = Vec::new();
+    if exponent >= 0 {
+        if exponent <= 21 {
+            candidates.push(format!("{}{}", digits, "0".repeat(exponent as usize)));
+        }
+    } else if -exponent < k {
+        let point = (k + exponent) as usize;
+        candidates.push(format!("{}.{}", &digits[..point], &digits[point..]));
+    } else if -exponent - k <= 21 {
+        candidates.push(format!(
+            "0.{}{}",
+            "0".repeat((-exponent - k) as usize),
+            digits
+        ));
+    }
+    candidates.push(format!("{}e{}", digits, exponent));
+    if k > 1 {
+        candidates.push(format!(
+            "{}.{}e{}",
+            &digits[..1],
+            &digits[1..],
+            exponent + k - 1
+        ));
+    }
+
+    // `min_by_key` keeps the first of several equally short candidates
+    candidates.into_iter().min_by_key(String::len).unwrap()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn shortest() {
+        let cases = [
+            ("0", "0"),
+            ("1", "1"),
+            ("1.0", "1"),
+            ("1.50", "1.5"),
+            ("-1.50", "-1.5"),
+            ("1e+03", "1e3"),
+            ("1E3", "1e3"),
+            ("100", "100"),
+            ("1000", "1e3"),
+            ("0.000001", "1e-6"),
+            ("0.5", "0.5"),
+            ("0.05", "0.05"),
+            ("0.005", "5e-3"),
+            ("123.456e2", "12345.6"),
+            ("12e-10", "12e-10"),
+            ("1.2e-9", "12e-10"),
+            ("0.0", "0"),
+            ("00.0e5", "0"),
+            ("1234500000", "12345e5"),
+            ("1.7976931348623157e308", "17976931348623157e292"),
+            (
+                "123456789012345678901234567890",
+                "123456789012345678901234567890",
+            ),
+            (
+                "-123456789012345678901234567890.000",
+                "-123456789012345678901234567890",
+            ),
+            ("1e400", "1e400"),
+            ("-1e-400", "-1e-400"),
+            ("1e999999999999999999999", "1e999999999999999999999"),
+        ];
+        for &(number, shortest) in &cases {
+            assert_eq!(normalize(number.into(), true), shortest, "{}", number);
+        }
+    }
+
+    #[test]
+    fn negative_zero() {
+        assert_eq!(normalize("-0".into(), true), "-0");
+        assert_eq!(normalize("-0.00e10".into(), true), "-0");
+        assert_eq!(normalize("-0".into(), false), "0");
+        assert_eq!(normalize("-0.0".into(), false), "0");
+    }
+
+    #[test]
+    fn rounding() {
+        let significant = |number, digits| round(number, Precision::SignificantDigits(digits));
+        assert_eq!(
+            significant("10.123456789012345", 6),
+            Some("10.1235".to_string())
+        );
+        assert_eq!(significant("10.12", 6), None);
+        assert_eq!(significant("-0.00012345", 3), Some("-123e-6".to_string()));
+        assert_eq!(significant("0.00012355", 3), Some("124e-6".to_string()));
+        assert_eq!(significant("999.5", 3), Some("1e3".to_string()));
+        assert_eq!(significant("123456789", 2), Some("12e7".to_string()));
+
+        let places = |number, places| round(number, Precision::DecimalPlaces(places));
+        assert_eq!(
+            places("10.123456789012345", 6),
+            Some("10.123457".to_string())
+        );
+        assert_eq!(places("-73.9857", 2), Some("-73.99".to_string()));
+        assert_eq!(places("1.5e-3", 2), Some("0".to_string()));
+        assert_eq!(places("-4.9e-3", 2), Some("0".to_string()));
+        assert_eq!(places("5e-3", 2), Some("0.01".to_string()));
+        assert_eq!(places("0.0995", 3), Some("0.1".to_string()));
+        assert_eq!(places("12345", 0), None);
+        assert_eq!(places("12.5", 0), Some("13".to_string()));
+        assert_eq!(places("1.10", 1), None);
+        assert_eq!(
+            places("123456789012345678901234567890.55", 1),
+            Some("123456789012345678901234567890.6".to_string())
+        );
+
+        // Precisions too large to round anything
+        for number in &["1.5", "10.123456", "-1e-300", "123456789e300"] {
+            assert_eq!(significant(number, usize::MAX), None);
+            assert_eq!(places(number, usize::MAX), None);
+            assert_eq!(places(number, i64::MAX as usize), None);
+        }
+    }
+
+    #[test]
+    fn borrowed() {
+        assert!(matches!(normalize("1.5".into(), true), Cow::Borrowed(_)));
+        assert!(matches!(normalize("1.50".into(), true), Cow::Owned(_)));
+    }
+}
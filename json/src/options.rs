@@ -0,0 +1,148 @@
+use crate::error::Error;
+
+/// How far to round numbers, see `Options::precision`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Precision {
+    /// Keep this many significant digits, at least one.
+    SignificantDigits(usize),
+    /// Keep this many digits after the decimal point.
+    DecimalPlaces(usize),
+}
+
+/// How to write escapes and non-ASCII characters in strings, see
+/// `Options::escapes`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum Escapes {
+    /// Keep strings as written.
+    #[default]
+    Keep,
+    /// Decode every escape that is not needed, like `\u00e9` and `\/`, so
+    /// only the quote, `\` and control characters are escaped.
+    Decode,
+    /// Escape every non-ASCII character as `\uXXXX`, so the output is pure
+    /// ASCII.
+    Ascii,
+}
+
+/// What to do with keys that appear more than once in the same object, see
+/// `Options::duplicate_keys`.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub enum DuplicateKeys {
+    /// Keep every member.
+    #[default]
+    Allow,
+    /// Fail with `Error::DuplicateKey`.
+    Error,
+    /// Keep the first member with the key and drop the others.
+    KeepFirst,
+    /// Keep the last member with the key and drop the others. This holds
+    /// back the members of each object until it is closed.
+    KeepLast,
+}
+
+/// Limits on the size and shape of the input, to safely minify untrusted
+/// input. Going over one of them fails with `Error::LimitExceeded`. Nothing
+/// is limited by default.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Limits {
+    /// How deeply arrays and objects can be nested.
+    pub max_depth: Option<usize>,
+    /// The longest string or key, in bytes as written without the quotes.
+    pub max_string_bytes: Option<usize>,
+    /// The longest number, in bytes as written.
+    pub max_number_length: Option<usize>,
+    /// How many tokens the input can have.
+    pub max_tokens: Option<usize>,
+    /// How large the input can be, in bytes.
+    pub max_input_bytes: Option<usize>,
+}
+
+/// Options shared by `Minify` and the streaming `Minifier`.
+#[derive(Debug, Clone, Default)]
+pub struct Options {
+    /// Skip grammar validation and minify any sequence of valid tokens, so
+    /// input like `[1,]` or `}{` is passed through instead of rejected.
+    pub lenient: bool,
+    /// Accept `//` and `/* */` comments, as used in JSONC files like
+    /// `tsconfig.json`, and strip them from the output.
+    pub comments: bool,
+    /// Accept JSON5 input: comments, unquoted keys, single quoted and
+    /// multi-line strings, trailing commas, hexadecimal numbers, leading and
+    /// trailing decimal points, explicit `+` signs, `Infinity` and `NaN`.
+    /// The output is converted to strict JSON unless `keep_json5` is set.
+    pub json5: bool,
+    /// Write JSON5 input back out as compact JSON5 instead of converting it
+    /// to strict JSON.
+    pub keep_json5: bool,
+    /// Write the canonical form defined by RFC 8785 (JCS), so documents
+    /// with the same content always give the same bytes: object members are
+    /// sorted by key, and numbers and strings are normalized. The input has
+    /// to be valid, so this overrides `lenient` and `keep_json5`, and
+    /// duplicate keys fail unless `duplicate_keys` resolves them. Options
+    /// that would change the canonical form, `Escapes::Ascii` and
+    /// `html_safe`, fail with `Error::ConflictingOptions`.
+    pub canonical: bool,
+    /// Write numbers in their shortest form without changing their value,
+    /// like `1.50` as `1.5` and `0.000001` as `1e-6`. This works on the
+    /// digits, so numbers too large for a double keep every digit.
+    pub normalize_numbers: bool,
+    /// When normalizing numbers, write `-0` as `0`. Negative zero is kept by
+    /// default since it is a different value in IEEE 754.
+    pub normalize_negative_zero: bool,
+    /// Round numbers, rounding half away from zero. This is lossy, but can
+    /// make documents full of coordinates or measurements a lot smaller.
+    /// Rounded numbers are written in their shortest form.
+    pub precision: Option<Precision>,
+    /// Only round numbers at or below these locations, written as JSON
+    /// Pointers where `*` matches any key or index, like
+    /// `/features/*/geometry`. All numbers are rounded if this is empty.
+    /// Locations refer to the input document, like `drop_paths`.
+    pub precision_paths: Vec<String>,
+    /// How to write escapes and non-ASCII characters in strings and keys.
+    /// Canonical output always decodes escapes, as with `Escapes::Decode`.
+    pub escapes: Escapes,
+    /// Escape `<`, `>`, `&`, U+2028 and U+2029 in strings, so the output can
+    /// be embedded in a `<script>` element or in JavaScript source.
+    pub html_safe: bool,
+    /// What to do with keys that appear more than once in the same object,
+    /// which RFC 8259 leaves undefined. Keys are compared with their escapes
+    /// decoded. Every duplicate is reported by `Minify::duplicates`.
+    /// Duplicates are resolved before members are dropped, so the drop
+    /// options apply to the member that is kept.
+    pub duplicate_keys: DuplicateKeys,
+    /// Drop object members whose value is `null`. Array elements are always
+    /// kept, so indices do not change.
+    pub drop_nulls: bool,
+    /// Drop object members whose value is an empty array or object,
+    /// including ones that are only empty once their members are dropped.
+    pub drop_empty: bool,
+    /// Drop object members with these keys, wherever they are.
+    pub drop_keys: Vec<String>,
+    /// Drop object members at these locations, written as JSON Pointers
+    /// where `*` matches any key or index, like `/items/*/debug`. Locations
+    /// refer to the input document.
+    pub drop_paths: Vec<String>,
+    /// Limits on the size and shape of the input.
+    pub limits: Limits,
+}
+
+impl Options {
+    /// Options that cannot be used together.
+    pub(crate) fn conflict(&self) -> Option<Error> {
+        if !self.canonical {
+            None
+        } else if self.escapes == Escapes::Ascii {
+            Some(Error::ConflictingOptions(
+                "canonical output",
+                "ASCII escapes",
+            ))
+        } else if self.html_safe {
+            Some(Error::ConflictingOptions(
+                "canonical output",
+                "HTML-safe escapes",
+            ))
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::escape::unescape;
+use crate::tokens::Token;
+
+#[derive(Debug)]
+enum Frame {
+    /// The key of the current member, `None` while waiting for the key.
+    Object(Option<String>),
+    /// The index of the current element.
+    Array(usize),
+}
+
+/// Keeps track of where in the document the tokens being passed through are,
+/// so they can be matched against JSON Pointer patterns.
+#[derive(Debug, Default)]
+pub(crate) struct Path {
+    stack: Vec<Frame>,
+}
+
+impl Path {
+    /// Starts over at the beginning of a document.
+    pub fn reset(&mut self) {
+        self.stack.clear();
+    }
+
+    /// Moves past `token`.
+    pub fn push(&mut self, token: &Token) {
+        if let Some(Frame::Object(key @ None)) = self.stack.last_mut() {
+            if let Some(name) = key_name(token) {
+                *key = Some(name);
+                return;
+            }
+        }
+        match (self.stack.last_mut(), token) {
+            (Some(Frame::Object(key)), Token::Comma) => *key = None,
+            (Some(Frame::Array(index)), Token::Comma) => *index += 1,
+            (_, Token::ObjectStart) => self.stack.push(Frame::Object(None)),
+            (_, Token::ArrayStart) => self.stack.push(Frame::Array(0)),
+            (_, Token::ObjectEnd) | (_, Token::ArrayEnd) => {
+                self.stack.pop();
+            }
+            _ => {}
+        }
+    }
+
+    /// Whether the value the last token belongs to is at or below a location
+    /// matched by `pattern`.
+    pub fn within(&self, pattern: &Pattern) -> bool {
+        pattern.segments.len() <= self.stack.len()
+            && pattern
+                .segments
+                .iter()
+                .zip(&self.stack)
+                .all(|(segment, frame)| match (segment, frame) {
+                    (None, _) => true,
+                    (Some(segment), Frame::Object(Some(key))) => segment == key,
+                    (Some(segment), Frame::Array(index)) => *segment == index.to_string(),
+                    _ => false,
+                })
+    }
+}
+
+/// The decoded name of a token used as an object key, or `None` if the token
+/// cannot be a key.
+pub(crate) fn key_name(token: &Token) -> Option<String> {
+    match token {
+        Token::String(name) | Token::SingleQuoted(name) | Token::Identifier(name) => {
+            Some(unescape(name).into_owned())
+        }
+        // JSON5 also allows these as keys
+        Token::Null | Token::True | Token::False | Token::Number(_) => Some(token.to_string()),
+        _ => None,
+    }
+}
+
+/// A JSON Pointer (RFC 6901) where a `*` segment matches any key or index,
+/// like `/features/*/geometry`.
+#[derive(Debug, Clone)]
+pub(crate) struct Pattern {
+    /// `None` for a wildcard.
+    segments: Vec<Option<String>>,
+}
+
+impl Pattern {
+    pub fn new(pointer: &str) -> Self {
+        let segments = pointer
+            .split('/')
+            .skip(1)
+            .map(|segment| match segment {
+                "*" => None,
+                segment => Some(segment.replace("~1", "/").replace("~0", "~")),
+            })
+            .collect();
+
+        Pattern { segments }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tokens::Lexer;
+
+    /// Whether each number in `source` is within `pointer`.
+    fn matches(source: &str, pointer: &str) -> Vec<bool> {
+        let pattern = Pattern::new(pointer);
+        let mut lexer = Lexer::new(source);
+        let mut path = Path::default();
+        let mut matches = Vec::new();
+        loop {
+            let token = lexer.next_token().unwrap();
+            path.push(&token);
+            match token {
+                Token::Number(_) => matches.push(path.within(&pattern)),
+                Token::EndOfFile => return matches,
+                _ => {}
+            }
+        }
+    }
+
+    #[test]
+    fn pointers() {
+        let source = r#"{"a": 1, "b": [2, {"c": 3, "a/~": 4}], "d": {"0": 5}}"#;
+        assert_eq!(matches(source, ""), [true; 5]);
+        assert_eq!(matches(source, "/a"), [true, false, false, false, false]);
+        assert_eq!(matches(source, "/b"), [false, true, true, true, false]);
+        assert_eq!(matches(source, "/b/0"), [false, true, false, false, false]);
+        assert_eq!(
+            matches(source, "/b/1/c"),
+            [false, false, true, false, false]
+        );
+        assert_eq!(
+            matches(source, "/b/*/a~1~0"),
+            [false, false, false, true, false]
+        );
+        assert_eq!(matches(source, "/*/0"), [false, true, false, false, true]);
+        assert_eq!(matches(source, "/*/*/*"), [false, false, true, true, false]);
+        assert_eq!(matches(source, "/b/01"), [false; 5]);
+        assert_eq!(matches(r#"{"a": 1}"#, "/a"), [true]);
+    }
+}
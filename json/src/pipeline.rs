@@ -0,0 +1,524 @@
+use crate::canonical::Canonical;
+use crate::diagnostic::Diagnostic;
+use crate::duplicates::{Duplicate, Duplicates};
+use crate::error::{Error, Limit, Position};
+use crate::escape;
+use crate::filter::Filter;
+use crate::json5;
+use crate::number;
+use crate::options::{DuplicateKeys, Escapes, Options};
+use crate::path::{Path, Pattern};
+use crate::tokens::{self, Lexer, Settings, Span, Token};
+use crate::validate::Validator;
+use std::borrow::Cow;
+use std::mem;
+
+/// The steps between lexing and writing that `Minify` and the streaming
+/// `Minifier` share, applied one token at a time.
+pub(crate) struct Pipeline {
+    options: Options,
+    validator: Validator,
+    /// A comma that is held back until we know it is not a trailing comma.
+    comma: Option<Position>,
+    /// Members held back until we know they are not dropped, when filtering.
+    filter: Option<Filter>,
+    /// The keys seen in each open object, unless duplicates are allowed.
+    duplicates: Option<Duplicates>,
+    /// The document so far, when writing canonical output.
+    canonical: Option<Canonical>,
+    /// Where in the document we are, when rounding numbers in some places.
+    path: Option<Path>,
+    precision_paths: Vec<Pattern>,
+    /// How many numbers have been rounded.
+    rounded: usize,
+    /// How many arrays and objects are open.
+    depth: usize,
+    /// How many tokens have been read.
+    tokens: usize,
+    /// Whether tokens are passed in with `recover` rather than `push`.
+    recovering: bool,
+    /// The problems found in the input, when recovering from errors.
+    diagnostics: Vec<Diagnostic>,
+    /// Why the options cannot be used, if they cannot.
+    conflict: Option<Error>,
+}
+
+impl Pipeline {
+    pub fn new(options: Options) -> Self {
+        Pipeline {
+            validator: Validator::new().json5(options.json5),
+            filter: if options.drop_nulls
+                || options.drop_empty
+                || !options.drop_keys.is_empty()
+                || !options.drop_paths.is_empty()
+            {
+                Some(Filter::new(
+                    options.drop_nulls,
+                    options.drop_empty,
+                    &options.drop_keys,
+                    &options.drop_paths,
+                ))
+            } else {
+                None
+            },
+            duplicates: match options.duplicate_keys {
+                DuplicateKeys::Allow => None,
+                policy => Some(Duplicates::new(policy)),
+            },
+            canonical: if options.canonical {
+                Some(Canonical::default())
+            } else {
+                None
+            },
+            path: if options.precision_paths.is_empty() {
+                None
+            } else {
+                Some(Path::default())
+            },
+            precision_paths: options
+                .precision_paths
+                .iter()
+                .map(|pointer| Pattern::new(pointer))
+                .collect(),
+            conflict: options.conflict(),
+            options,
+            comma: None,
+            recovering: false,
+            diagnostics: Vec::new(),
+            rounded: 0,
+            depth: 0,
+            tokens: 0,
+        }
+    }
+
+    /// Makes the pipeline hold back commas even in JSON, so trailing commas
+    /// can be dropped, for use with `recover`.
+    pub fn recovering(mut self) -> Self {
+        self.recovering = true;
+        self
+    }
+
+    /// Starts over at the beginning of a new document with the same options,
+    /// for minifying many documents without setting up the pipeline again.
+    /// Duplicates found so far are kept, the rest of the state is cleared.
+    pub fn reset(&mut self) {
+        self.validator.reset();
+        self.comma = None;
+        if let Some(filter) = &mut self.filter {
+            filter.reset();
+        }
+        if let Some(duplicates) = &mut self.duplicates {
+            duplicates.reset();
+        }
+        if let Some(canonical) = &mut self.canonical {
+            canonical.reset();
+        }
+        if let Some(path) = &mut self.path {
+            path.reset();
+        }
+        self.depth = 0;
+        self.tokens = 0;
+    }
+
+    /// How many numbers have been changed by rounding them.
+    pub fn rounded(&self) -> usize {
+        self.rounded
+    }
+
+    /// The duplicate keys found since the last call.
+    pub fn take_duplicates(&mut self) -> Vec<Duplicate> {
+        match &mut self.duplicates {
+            Some(duplicates) => duplicates.take_found(),
+            None => Vec::new(),
+        }
+    }
+
+    /// Whether the output is JSON5, in which case numbers do not follow the
+    /// JSON grammar.
+    fn json5_output(&self) -> bool {
+        self.options.json5 && self.options.keep_json5 && !self.options.canonical
+    }
+
+    /// A lexer for `input`, which starts at `position` in the document.
+    pub fn lexer<'a>(&self, input: &'a str, position: Position) -> Lexer<'a> {
+        Lexer::new(input)
+            .starting_at(position)
+            .with_settings(self.lexer_settings())
+    }
+
+    /// How the lexer has to read the input for these options.
+    pub fn lexer_settings(&self) -> Settings {
+        Settings {
+            comments: self.options.comments,
+            json5: self.options.json5,
+            decode: self.options.escapes == Escapes::Decode,
+            limits: self.options.limits,
+        }
+    }
+
+    /// Processes the next token, which starts at `position`, and passes the
+    /// tokens that should be written to `emit`.
+    pub fn push<'a, E, F>(&mut self, token: Token<'a>, position: Position, emit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        self.check_options()?;
+        self.check_limits(&token, position)?;
+        let key = self.validator.expects_key();
+        match self.validator.check(&token, position) {
+            // Keep track of keys and values in lenient mode, as the later
+            // steps need to know which is which
+            Err(_) if self.options.lenient && !self.options.canonical => {
+                self.validator.advance(&token)
+            }
+            result => result?,
+        }
+        self.forward(token, key, position, emit)
+    }
+
+    /// Like `push`, but instead of failing on a token the grammar does not
+    /// allow, fixes the input as well as it can and records a diagnostic.
+    pub fn recover<'a, E, F>(&mut self, token: Token<'a>, span: Span, mut emit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        let position = span.start;
+        self.check_options()?;
+        self.check_limits(&token, position)?;
+        if self.validator.clone().check(&token, position).is_ok() {
+            return self.accept(token, position, emit);
+        }
+        let here = Span {
+            start: position,
+            end: position,
+        };
+
+        if let (Some(comma), Token::ObjectEnd | Token::ArrayEnd) = (self.comma, &token) {
+            let mut validator = self.validator.clone();
+            validator.undo_comma();
+            if validator.check(&token, position).is_ok() {
+                let mut end = comma;
+                end.advance(',');
+                self.diagnose(
+                    Span { start: comma, end },
+                    "trailing comma".to_string(),
+                    "remove the ','",
+                );
+                self.validator.undo_comma();
+                return self.accept(token, position, emit);
+            }
+        }
+
+        if token == Token::EndOfFile {
+            return self.finish_recovering(here, emit);
+        }
+
+        for (missing, message, suggestion) in [
+            (Token::Comma, "missing ','", "insert ','"),
+            (Token::Colon, "missing ':'", "insert ':'"),
+        ] {
+            let mut validator = self.validator.clone();
+            if validator.check(&missing, position).is_ok()
+                && validator.check(&token, position).is_ok()
+            {
+                self.diagnose(here, message.to_string(), suggestion);
+                self.accept(missing, position, &mut emit)?;
+                return self.accept(token, position, emit);
+            }
+        }
+
+        let other = match token {
+            Token::ObjectEnd => Some((Token::ArrayEnd, "replace it with ']'")),
+            Token::ArrayEnd => Some((Token::ObjectEnd, "replace it with '}'")),
+            _ => None,
+        };
+        if let Some((other, suggestion)) = other {
+            if self.validator.clone().check(&other, position).is_ok() {
+                let message = format!("mismatched {}", token.describe());
+                self.diagnose(span, message, suggestion);
+                return self.accept(other, position, emit);
+            }
+        }
+
+        let message = format!("unexpected {}", token.describe());
+        self.diagnose(span, message, "remove it");
+        Ok(())
+    }
+
+    /// Records a token the lexer could not read and skipped, and puts `null`
+    /// in its place if it had to be a value.
+    pub fn skipped<'a, E, F>(&mut self, span: Span, error: &Error, emit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        if !self.validator.expects_value() {
+            self.diagnostics.push(Diagnostic {
+                span,
+                message: error.message(),
+                suggestion: None,
+            });
+            return Ok(());
+        }
+
+        self.diagnose(span, error.message(), "replace it with a value");
+        self.accept(Token::Null, span.start, emit)
+    }
+
+    /// The diagnostics recorded since the last call.
+    pub fn take_diagnostics(&mut self) -> Vec<Diagnostic> {
+        mem::take(&mut self.diagnostics)
+    }
+
+    /// Completes a document that ends too early by closing what is still
+    /// open.
+    fn finish_recovering<'a, E, F>(&mut self, here: Span, mut emit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        let position = here.start;
+        if self.comma.take().is_some() {
+            self.validator.undo_comma();
+        }
+        let mut inserted = String::new();
+        while self
+            .validator
+            .clone()
+            .check(&Token::EndOfFile, position)
+            .is_err()
+        {
+            let missing = [Token::ObjectEnd, Token::ArrayEnd, Token::Colon, Token::Null]
+                .iter()
+                .find(|token| self.validator.clone().check(token, position).is_ok())
+                .cloned();
+            match missing {
+                Some(token) => {
+                    inserted.push_str(&token.to_string());
+                    self.accept(token, position, &mut emit)?;
+                }
+                // Only the end of the input is left
+                None => break,
+            }
+        }
+        if !inserted.is_empty() {
+            let suggestion = format!("insert '{}'", inserted);
+            self.diagnose(here, "unexpected end of input".to_string(), &suggestion);
+        }
+
+        self.accept(Token::EndOfFile, position, emit)
+    }
+
+    fn diagnose(&mut self, span: Span, message: String, suggestion: &str) {
+        self.diagnostics.push(Diagnostic {
+            span,
+            message,
+            suggestion: Some(suggestion.to_string()),
+        });
+    }
+
+    /// Passes on a token the grammar allows here.
+    fn accept<'a, E, F>(&mut self, token: Token<'a>, position: Position, emit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        let key = self.validator.expects_key();
+        self.validator.check(&token, position)?;
+        self.forward(token, key, position, emit)
+    }
+
+    /// Passes on a token that has been validated, `key` telling whether it
+    /// is an object key.
+    fn forward<'a, E, F>(
+        &mut self,
+        token: Token<'a>,
+        key: bool,
+        position: Position,
+        mut emit: F,
+    ) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        if token == Token::EndOfFile {
+            return match &mut self.canonical {
+                Some(canonical) => canonical.finish(emit),
+                None => Ok(()),
+            };
+        }
+        if !self.options.json5 && !self.recovering {
+            return self.emit(token, position, emit);
+        }
+
+        let token = if !self.options.json5 || self.json5_output() {
+            token
+        } else {
+            json5::to_json(token, key, position)?
+        };
+
+        // Trailing commas are dropped in both JSON and compact JSON5 output
+        let comma = self.comma.take();
+        if let Some(comma) = comma {
+            if token != Token::ObjectEnd && token != Token::ArrayEnd {
+                self.emit(Token::Comma, comma, &mut emit)?;
+            }
+        }
+        if token == Token::Comma {
+            self.comma = Some(position);
+            return Ok(());
+        }
+
+        self.emit(token, position, emit)
+    }
+
+    /// Rounds numbers and resolves duplicate keys first, so that locations
+    /// in `precision_paths` and `drop_paths` both refer to the input, and
+    /// dropping members with the filter applies to the members that are
+    /// kept.
+    fn emit<'a, E, F>(&mut self, token: Token<'a>, position: Position, mut emit: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        let token = self.round(token);
+        let mut duplicates = match self.duplicates.take() {
+            Some(duplicates) => duplicates,
+            None => return self.filter(token, position, emit),
+        };
+        let result = duplicates.push(token, position, |token, position| {
+            self.filter(token, position, &mut emit)
+        });
+        self.duplicates = Some(duplicates);
+
+        result
+    }
+
+    /// Drops members before writing out a token.
+    fn filter<'a, E, F>(
+        &mut self,
+        token: Token<'a>,
+        position: Position,
+        mut emit: F,
+    ) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        let mut filter = match self.filter.take() {
+            Some(filter) => filter,
+            None => return self.write(token, position, emit),
+        };
+        let result = filter.push(token, position, |token, position| {
+            self.write(token, position, &mut emit)
+        });
+        self.filter = Some(filter);
+
+        result
+    }
+
+    /// Fails on every token if the options cannot be used together.
+    fn check_options(&self) -> Result<(), Error> {
+        match &self.conflict {
+            Some(error) => Err(error.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// Checks the limits the lexer cannot check on its own.
+    fn check_limits(&mut self, token: &Token, position: Position) -> Result<(), Error> {
+        let limits = &self.options.limits;
+        match token {
+            Token::ObjectStart | Token::ArrayStart => self.depth += 1,
+            Token::ObjectEnd | Token::ArrayEnd => self.depth = self.depth.saturating_sub(1),
+            Token::EndOfFile => return Ok(()),
+            _ => {}
+        }
+        self.tokens += 1;
+
+        if tokens::exceeds(limits.max_depth, self.depth) {
+            Err(Error::LimitExceeded(Limit::Depth, position))
+        } else if tokens::exceeds(limits.max_tokens, self.tokens) {
+            Err(Error::LimitExceeded(Limit::Tokens, position))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Writes out a token, or adds it to the document when writing canonical
+    /// output.
+    fn write<'a, E, F>(
+        &mut self,
+        token: Token<'a>,
+        position: Position,
+        mut emit: F,
+    ) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token<'a>) -> Result<(), E>,
+    {
+        if let Some(canonical) = &mut self.canonical {
+            return Ok(canonical.push(token, position)?);
+        }
+
+        match token {
+            Token::Number(number) if self.options.normalize_numbers && !self.json5_output() => {
+                let negative_zero = !self.options.normalize_negative_zero;
+                emit(Token::Number(number::normalize(number, negative_zero)))
+            }
+            // JSON5 also allows `\uXXXX` escapes in single quoted strings and
+            // unquoted keys
+            Token::String(string) => emit(Token::String(self.escape(string))),
+            Token::SingleQuoted(string) => emit(Token::SingleQuoted(self.escape(string))),
+            Token::Identifier(name) => emit(Token::Identifier(self.escape(name))),
+            token => emit(token),
+        }
+    }
+
+    /// Adds the escapes asked for by the options to the body of a string.
+    fn escape<'a>(&self, string: Cow<'a, str>) -> Cow<'a, str> {
+        let string = if self.options.escapes == Escapes::Ascii {
+            escape::escape_non_ascii(string)
+        } else {
+            string
+        };
+        if self.options.html_safe {
+            escape::escape_html(string)
+        } else {
+            string
+        }
+    }
+
+    /// Rounds a number as asked by `Options::precision`.
+    fn round<'a>(&mut self, token: Token<'a>) -> Token<'a> {
+        if let Some(path) = &mut self.path {
+            path.push(&token);
+        }
+        match (token, self.options.precision) {
+            (Token::Number(number), Some(precision))
+                if !self.json5_output() && self.in_precision_paths() =>
+            {
+                match number::round(&number, precision) {
+                    Some(rounded) => {
+                        self.rounded += 1;
+                        Token::Number(rounded.into())
+                    }
+                    None => Token::Number(number),
+                }
+            }
+            (token, _) => token,
+        }
+    }
+
+    fn in_precision_paths(&self) -> bool {
+        match &self.path {
+            Some(path) => self
+                .precision_paths
+                .iter()
+                .any(|pattern| path.within(pattern)),
+            None => true,
+        }
+    }
+}
@@ -0,0 +1,330 @@
+use crate::duplicates::Duplicate;
+use crate::error::{Error, Position};
+use crate::options::Options;
+use crate::pipeline::Pipeline;
+use crate::tokens::Token;
+use std::fmt::{self, Write};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Indent {
+    /// Indent with the given number of spaces per level.
+    Spaces(usize),
+    /// Indent with one tab per level.
+    Tabs,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Newline {
+    Lf,
+    CrLf,
+}
+
+/// How `Prettify` lays out its output.
+#[derive(Debug, Clone)]
+pub struct Style {
+    pub indent: Indent,
+    pub newline: Newline,
+    /// Put a space between the colon and the value in objects.
+    pub space_after_colon: bool,
+    /// Keep arrays and objects on a single line if they fit within this many
+    /// columns, counting the indentation and everything before them.
+    pub max_width: Option<usize>,
+}
+
+impl Default for Style {
+    fn default() -> Self {
+        Style {
+            indent: Indent::Spaces(2),
+            newline: Newline::Lf,
+            space_after_colon: true,
+            max_width: None,
+        }
+    }
+}
+
+/// The inverse of `Minify`, lays out JSON with one member or element per
+/// line.
+#[derive(Debug)]
+pub struct Prettify<'a> {
+    tokens: Vec<Token<'a>>,
+    style: Style,
+    duplicates: Vec<Duplicate>,
+}
+
+impl<'a> Prettify<'a> {
+    pub fn new(input: &'a str) -> Result<Self, Error> {
+        Prettify::with_options(input, Options::default(), Style::default())
+    }
+
+    pub fn with_options(input: &'a str, options: Options, style: Style) -> Result<Self, Error> {
+        let mut pipeline = Pipeline::new(options);
+        let mut lexer = pipeline.lexer(input, Position::default());
+        let mut tokens = Vec::new();
+        loop {
+            let token = lexer.next_token()?;
+            let end = token == Token::EndOfFile;
+            pipeline.push(token, lexer.start(), |token| {
+                tokens.push(token);
+                Ok::<_, Error>(())
+            })?;
+            if end {
+                return Ok(Prettify {
+                    tokens,
+                    style,
+                    duplicates: pipeline.take_duplicates(),
+                });
+            }
+        }
+    }
+
+    /// Every key that appeared more than once in the same object, unless
+    /// `Options::duplicate_keys` allows them.
+    pub fn duplicates(&self) -> &[Duplicate] {
+        &self.duplicates
+    }
+
+    /// The index of the token closing each array or object, if any.
+    fn closing(&self) -> Vec<Option<usize>> {
+        let mut closing = vec![None; self.tokens.len()];
+        let mut open = Vec::new();
+        for (i, token) in self.tokens.iter().enumerate() {
+            match token {
+                Token::ObjectStart | Token::ArrayStart => open.push(i),
+                Token::ObjectEnd | Token::ArrayEnd => {
+                    if let Some(start) = open.pop() {
+                        closing[start] = Some(i);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        closing
+    }
+
+    /// Writes `tokens` on a single line and returns how many columns it
+    /// took, or fails as soon as that is more than `max`.
+    fn write_inline<W: Write>(
+        &self,
+        f: W,
+        tokens: &[Token],
+        max: usize,
+    ) -> Result<usize, fmt::Error> {
+        let mut f = Columns {
+            inner: f,
+            width: 0,
+            max,
+        };
+        for token in tokens {
+            write!(f, "{}", token)?;
+            match token {
+                Token::Comma => f.write_char(' ')?,
+                Token::Colon if self.style.space_after_colon => f.write_char(' ')?,
+                _ => {}
+            }
+        }
+
+        Ok(f.width)
+    }
+
+    /// Starts a new line at `depth` and returns the current column.
+    fn newline(&self, f: &mut fmt::Formatter, depth: usize) -> Result<usize, fmt::Error> {
+        f.write_str(match self.style.newline {
+            Newline::Lf => "\n",
+            Newline::CrLf => "\r\n",
+        })?;
+        let (unit, width) = match self.style.indent {
+            Indent::Spaces(width) => (" ", width),
+            // Tabs are counted as a single column
+            Indent::Tabs => ("\t", 1),
+        };
+        for _ in 0..depth * width {
+            f.write_str(unit)?;
+        }
+
+        Ok(depth * width)
+    }
+}
+
+impl fmt::Display for Prettify<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let closing = self.closing();
+        let mut depth = 0;
+        let mut column = 0;
+        let mut i = 0;
+        while i < self.tokens.len() {
+            let token = &self.tokens[i];
+            match token {
+                Token::ObjectStart | Token::ArrayStart => {
+                    if let Some(end) = closing[i] {
+                        let inline = &self.tokens[i..=end];
+                        // Only measured as far as the line goes, so this takes
+                        // at most `max_width` columns of work per container
+                        let fits = self.style.max_width.is_some_and(|max_width| {
+                            let room = max_width.saturating_sub(column);
+                            self.write_inline(Discard, inline, room).is_ok()
+                        });
+                        if end == i + 1 || fits {
+                            column += self.write_inline(&mut *f, inline, usize::MAX)?;
+                            i = end + 1;
+                            continue;
+                        }
+                    }
+
+                    write!(f, "{}", token)?;
+                    depth += 1;
+                    column = self.newline(f, depth)?;
+                }
+                Token::ObjectEnd | Token::ArrayEnd => {
+                    depth = depth.saturating_sub(1);
+                    column = self.newline(f, depth)? + 1;
+                    write!(f, "{}", token)?;
+                }
+                Token::Comma => {
+                    f.write_char(',')?;
+                    column = self.newline(f, depth)?;
+                }
+                token => {
+                    column += self.write_inline(&mut *f, std::slice::from_ref(token), usize::MAX)?
+                }
+            }
+            i += 1;
+        }
+
+        Ok(())
+    }
+}
+
+/// Passes text on and counts the columns it takes, failing once they are
+/// more than `max`.
+struct Columns<W> {
+    inner: W,
+    width: usize,
+    max: usize,
+}
+
+impl<W: Write> Write for Columns<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        // No need to count the whole of a long string
+        let room = self.max - self.width;
+        let width = s.chars().take(room.saturating_add(1)).count();
+        if width > room {
+            return Err(fmt::Error);
+        }
+        self.width += width;
+        self.inner.write_str(s)
+    }
+}
+
+/// A writer that only measures.
+struct Discard;
+
+impl Write for Discard {
+    fn write_str(&mut self, _: &str) -> fmt::Result {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::minify::Minify;
+
+    const INPUT: &str =
+        r#"{"name":"ola nordmann","age":100,"tags":[],"messages":["hello","world",{"a":null}]}"#;
+
+    fn prettify(input: &str, style: Style) -> String {
+        format!(
+            "{}",
+            Prettify::with_options(input, Options::default(), style).unwrap()
+        )
+    }
+
+    #[test]
+    fn default() {
+        let expected = r#"{
+  "name": "ola nordmann",
+  "age": 100,
+  "tags": [],
+  "messages": [
+    "hello",
+    "world",
+    {
+      "a": null
+    }
+  ]
+}"#;
+        assert_eq!(expected, format!("{}", Prettify::new(INPUT).unwrap()));
+        assert_eq!(
+            INPUT,
+            format!("{}", Minify::new(expected).unwrap()),
+            "minifying the output gives back the input"
+        );
+    }
+
+    #[test]
+    fn styles() {
+        let style = Style {
+            indent: Indent::Tabs,
+            newline: Newline::CrLf,
+            space_after_colon: false,
+            max_width: None,
+        };
+        assert_eq!(
+            "[\r\n\t1,\r\n\t{\r\n\t\t\"a\":true\r\n\t}\r\n]",
+            prettify(r#"[1, {"a": true}]"#, style)
+        );
+
+        let style = Style {
+            indent: Indent::Spaces(4),
+            ..Style::default()
+        };
+        assert_eq!(
+            "{\n    \"a\": [\n        1\n    ]\n}",
+            prettify(r#"{"a":[1]}"#, style)
+        );
+    }
+
+    #[test]
+    fn compact() {
+        let style = Style {
+            max_width: Some(30),
+            ..Style::default()
+        };
+        let expected = r#"{
+  "name": "ola nordmann",
+  "age": 100,
+  "tags": [],
+  "messages": [
+    "hello",
+    "world",
+    {"a": null}
+  ]
+}"#;
+        assert_eq!(expected, prettify(INPUT, style));
+
+        let style = Style {
+            max_width: Some(100),
+            ..Style::default()
+        };
+        assert_eq!(
+            r#"{"name": "ola nordmann", "age": 100, "tags": [], "messages": ["hello", "world", {"a": null}]}"#,
+            prettify(INPUT, style)
+        );
+
+        // Measuring stops at the end of the line
+        let mut columns = Columns {
+            inner: String::new(),
+            width: 0,
+            max: 5,
+        };
+        assert!(columns.write_str("[\"é").is_ok());
+        assert!(columns.write_str(&"a".repeat(1000)).is_err());
+        assert_eq!((columns.inner.as_str(), columns.width), ("[\"é", 3));
+    }
+
+    #[test]
+    fn scalar() {
+        assert_eq!("\"a\"", format!("{}", Prettify::new(" \"a\" ").unwrap()));
+    }
+}
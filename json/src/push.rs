@@ -0,0 +1,568 @@
+//! Lexing and minifying input that arrives in pieces, like JSON read from a
+//! socket.
+//!
+//! Input is pushed in with `feed` as it arrives, split anywhere, even in the
+//! middle of a string, a number or a UTF-8 sequence. Every token that is
+//! complete is handled right away and the rest is kept until the next call,
+//! so memory use is bounded by the largest single token. `finish` ends the
+//! input and fails if it stopped in the middle of a token.
+
+use crate::duplicates::Duplicate;
+use crate::encoding::Encoding;
+use crate::error::{Error, Position};
+use crate::options::{Limits, Options};
+use crate::pipeline::Pipeline;
+use crate::scan;
+use crate::tokens::{self, Settings, Span, Token};
+use std::io::{self, Write};
+use std::str;
+
+/// A lexer that is fed input as it arrives.
+#[derive(Debug, Default)]
+pub struct Lexer {
+    /// Input that has not been turned into tokens yet.
+    buffer: String,
+    /// The start of a UTF-8 sequence that continues in the next piece.
+    partial: Vec<u8>,
+    /// Where `buffer` starts in the input.
+    position: Position,
+    settings: Settings,
+    /// The token at the end of `buffer` that is still open, if we know
+    /// what it is.
+    open: Option<Open>,
+}
+
+/// A token that ran into the end of the buffer, and how far it has been
+/// scanned for its end, so a token split over many pieces is not lexed
+/// again for every piece.
+#[derive(Debug, Clone, Copy)]
+struct Open {
+    /// Where the token starts in the buffer.
+    start: usize,
+    /// How far into the buffer it has been scanned.
+    scanned: usize,
+    kind: OpenKind,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum OpenKind {
+    /// A string, `escape` is set when the last byte scanned is a backslash.
+    String { quote: u8, escape: bool },
+    /// A number, literal or JSON5 identifier.
+    Word,
+    /// A block comment, `star` is set when the last byte scanned is `*`.
+    Comment { star: bool },
+    /// A line comment.
+    LineComment,
+}
+
+impl Lexer {
+    pub fn new() -> Self {
+        Lexer::default()
+    }
+
+    pub(crate) fn with_settings(settings: Settings) -> Self {
+        Lexer {
+            settings,
+            ..Lexer::default()
+        }
+    }
+
+    /// Whether `//` and `/* */` comments are skipped like whitespace.
+    pub fn comments(mut self, comments: bool) -> Self {
+        self.settings.comments = comments;
+        self
+    }
+
+    /// Whether to accept JSON5 instead of JSON, this includes comments.
+    pub fn json5(mut self, json5: bool) -> Self {
+        self.settings.json5 = json5;
+        self
+    }
+
+    /// Whether to decode every escape in strings that is not needed.
+    pub fn decode_escapes(mut self, decode: bool) -> Self {
+        self.settings.decode = decode;
+        self
+    }
+
+    /// Limits on the length of strings and numbers and on the size of the
+    /// input.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.settings.limits = limits;
+        self
+    }
+
+    /// Adds the next piece of input and passes every token it completes to
+    /// `token`, along with its span in the whole input.
+    pub fn feed<E, F>(&mut self, bytes: &[u8], token: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token, Span) -> Result<(), E>,
+    {
+        self.partial.extend_from_slice(bytes);
+        let valid = match str::from_utf8(&self.partial) {
+            Ok(valid) => valid.len(),
+            Err(e) if e.error_len().is_none() => e.valid_up_to(),
+            Err(e) => return Err(self.invalid_utf8(e.valid_up_to()).into()),
+        };
+        self.buffer
+            .push_str(str::from_utf8(&self.partial[..valid]).unwrap());
+        self.partial.drain(..valid);
+
+        if self.scan_open() {
+            self.drain(false, token)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Ends the input and passes the remaining tokens to `token`, the last
+    /// one being `Token::EndOfFile`. Fails if the input ends in the middle
+    /// of a token.
+    pub fn finish<E, F>(&mut self, token: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token, Span) -> Result<(), E>,
+    {
+        if !self.partial.is_empty() {
+            return Err(self.invalid_utf8(0).into());
+        }
+
+        self.open = None;
+        self.drain(true, token)
+    }
+
+    /// Passes on every complete token in the buffer. Unless `eof` is set, a
+    /// token that runs into the end of the buffer is kept since it might
+    /// continue in the next piece.
+    fn drain<E, F>(&mut self, eof: bool, mut token: F) -> Result<(), E>
+    where
+        E: From<Error>,
+        F: FnMut(Token, Span) -> Result<(), E>,
+    {
+        let mut lexer = tokens::Lexer::new(&self.buffer)
+            .starting_at(self.position)
+            .with_settings(self.settings);
+        let mut consumed = self.position;
+        let mut open = None;
+        loop {
+            let next = lexer.next_token();
+            // Limits are checked before the token is complete, so the buffer
+            // does not grow without bounds
+            let limited = matches!(next, Err(Error::LimitExceeded(..)));
+            if lexer.reached_end() && !eof && !limited {
+                // Nothing but whitespace is left, which can go, unless it
+                // ends in a line comment that might continue
+                if next == Ok(Token::EndOfFile) {
+                    let rest = &self.buffer[consumed.offset - self.position.offset..];
+                    match open_line_comment(rest.as_bytes()) {
+                        Some(start) => open = Some(consumed.offset + start),
+                        None => consumed = lexer.start(),
+                    }
+                } else {
+                    open = Some(lexer.start().offset);
+                }
+                break;
+            }
+
+            let next = next?;
+            let end = next == Token::EndOfFile;
+            let span = Span {
+                start: lexer.start(),
+                end: lexer.position(),
+            };
+            token(next, span)?;
+            consumed = lexer.position();
+            if end {
+                break;
+            }
+        }
+
+        self.buffer.drain(..consumed.offset - self.position.offset);
+        self.position = consumed;
+        self.open = open.and_then(|start| self.open_at(start - consumed.offset));
+        self.scan_open();
+        Ok(())
+    }
+
+    /// The token starting at `start` in the buffer, which ran into its end,
+    /// unless it is one that is cheap enough to lex again.
+    fn open_at(&self, start: usize) -> Option<Open> {
+        let bytes = &self.buffer.as_bytes()[start..];
+        let (kind, length) = match bytes {
+            [b'"', ..] => (
+                OpenKind::String {
+                    quote: b'"',
+                    escape: false,
+                },
+                1,
+            ),
+            [b'\'', ..] if self.settings.json5 => (
+                OpenKind::String {
+                    quote: b'\'',
+                    escape: false,
+                },
+                1,
+            ),
+            [b'/', b'*', ..] => (OpenKind::Comment { star: false }, 2),
+            [b'/', b'/', ..] => (OpenKind::LineComment, 2),
+            [byte, ..] if is_word_byte(*byte) => (OpenKind::Word, 1),
+            _ => return None,
+        };
+        Some(Open {
+            start,
+            scanned: start + length,
+            kind,
+        })
+    }
+
+    /// Scans the input added since the last call for the end of the open
+    /// token. Returns whether the buffer has to be lexed again, because the
+    /// token may have ended or gone over one of the limits, or because there
+    /// is no open token.
+    ///
+    /// Only a byte that cannot continue the token counts as its end, so an
+    /// invalid token is reported once it ends rather than where the lexer
+    /// would first notice.
+    fn scan_open(&mut self) -> bool {
+        let open = match &mut self.open {
+            Some(open) => open,
+            None => return true,
+        };
+        let bytes = self.buffer.as_bytes();
+        let ended = match &mut open.kind {
+            OpenKind::String { quote, escape } => loop {
+                if *escape {
+                    if open.scanned == bytes.len() {
+                        break false;
+                    }
+                    // Any other bytes of the escape are not special
+                    open.scanned += 1;
+                    *escape = false;
+                }
+                open.scanned += scan::string_run(&bytes[open.scanned..], *quote);
+                match bytes.get(open.scanned) {
+                    None => break false,
+                    Some(b'\\') => *escape = true,
+                    // JSON5 allows most control characters, but not line breaks
+                    Some(&byte) if byte < 0x20 && byte != b'\n' && byte != b'\r' => {
+                        if !self.settings.json5 {
+                            break true;
+                        }
+                    }
+                    Some(_) => break true,
+                }
+                open.scanned += 1;
+            },
+            OpenKind::Word => {
+                let rest = &bytes[open.scanned..];
+                let run = rest.iter().take_while(|&&byte| is_word_byte(byte)).count();
+                open.scanned += run;
+                run < rest.len()
+            }
+            OpenKind::Comment { star } => loop {
+                match bytes.get(open.scanned) {
+                    None => break false,
+                    Some(b'/') if *star => break true,
+                    Some(&byte) => *star = byte == b'*',
+                }
+                open.scanned += 1;
+            },
+            OpenKind::LineComment => {
+                let rest = &bytes[open.scanned..];
+                match rest.iter().position(|&byte| byte == b'\n') {
+                    Some(end) => {
+                        open.scanned += end;
+                        true
+                    }
+                    None => {
+                        open.scanned = bytes.len();
+                        false
+                    }
+                }
+            }
+        };
+
+        let limits = self.settings.limits;
+        let length = open.scanned - open.start;
+        let limited = tokens::exceeds(limits.max_input_bytes, self.position.offset + bytes.len())
+            || match open.kind {
+                OpenKind::String { .. } => tokens::exceeds(limits.max_string_bytes, length - 1),
+                OpenKind::Word => {
+                    tokens::exceeds(limits.max_string_bytes, length)
+                        || tokens::exceeds(limits.max_number_length, length)
+                }
+                OpenKind::Comment { .. } | OpenKind::LineComment => false,
+            };
+        if ended || limited {
+            self.open = None;
+        }
+        self.open.is_none()
+    }
+
+    /// The error for the invalid bytes at `valid` in `partial`.
+    fn invalid_utf8(&self, valid: usize) -> Error {
+        let mut position = self.position;
+        self.buffer.chars().for_each(|c| position.advance(c));
+        let valid = str::from_utf8(&self.partial[..valid]).unwrap();
+        valid.chars().for_each(|c| position.advance(c));
+        Error::InvalidEncoding(Encoding::Utf8, position)
+    }
+}
+
+/// Where the line comment that `trivia`, which is only whitespace and
+/// complete comments otherwise, ends in starts, if it has no line break yet.
+fn open_line_comment(trivia: &[u8]) -> Option<usize> {
+    let mut i = 0;
+    while i < trivia.len() {
+        match &trivia[i..] {
+            [b'/', b'/', rest @ ..] => match rest.iter().position(|&byte| byte == b'\n') {
+                Some(end) => i += 2 + end + 1,
+                None => return Some(i),
+            },
+            [b'/', b'*', rest @ ..] => {
+                let end = rest.windows(2).position(|pair| pair == b"*/");
+                i += 2 + end.map_or(rest.len(), |end| end + 2);
+            }
+            _ => i += 1,
+        }
+    }
+
+    None
+}
+
+/// Whether `byte` can be part of a number, a literal or a JSON5 identifier.
+fn is_word_byte(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"+-._$\\".contains(&byte) || !byte.is_ascii()
+}
+
+/// A minifier that is fed input as it arrives, and writes out the minified
+/// form of every token as soon as it is complete.
+pub struct Minifier {
+    lexer: Lexer,
+    pipeline: Pipeline,
+}
+
+impl Minifier {
+    pub fn new() -> Self {
+        Minifier::with_options(Options::default())
+    }
+
+    pub fn with_options(options: Options) -> Self {
+        let pipeline = Pipeline::new(options);
+        Minifier {
+            lexer: Lexer::with_settings(pipeline.lexer_settings()),
+            pipeline,
+        }
+    }
+
+    /// Adds the next piece of input and writes out what it completes.
+    pub fn feed<W: Write>(&mut self, bytes: &[u8], writer: &mut W) -> io::Result<()> {
+        let pipeline = &mut self.pipeline;
+        self.lexer.feed(bytes, |token, span| {
+            pipeline.push(token, span.start, |token| write!(writer, "{}", token))
+        })
+    }
+
+    /// Ends the input and writes out the rest. Fails if the input ends in
+    /// the middle of a token or of the document.
+    pub fn finish<W: Write>(&mut self, writer: &mut W) -> io::Result<()> {
+        let pipeline = &mut self.pipeline;
+        self.lexer.finish(|token, span| {
+            pipeline.push(token, span.start, |token| write!(writer, "{}", token))
+        })
+    }
+
+    /// The duplicate keys found since the last call, see
+    /// `Options::duplicate_keys`.
+    pub fn take_duplicates(&mut self) -> Vec<Duplicate> {
+        self.pipeline.take_duplicates()
+    }
+}
+
+impl Default for Minifier {
+    fn default() -> Self {
+        Minifier::new()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Limit;
+
+    #[test]
+    fn split_tokens() {
+        let input = "{\"wørld\": [true, null, -12.5e3, \"a\\u0041\"]}";
+        for split in 0..=input.len() {
+            let (first, second) = input.as_bytes().split_at(split);
+            let mut lexer = Lexer::new();
+            let mut tokens = Vec::new();
+            let mut push = |token: Token, span: Span| {
+                tokens.push((token.into_owned(), span.start.offset));
+                Ok::<_, Error>(())
+            };
+            lexer.feed(first, &mut push).unwrap();
+            lexer.feed(second, &mut push).unwrap();
+            lexer.finish(&mut push).unwrap();
+
+            let expected: Vec<_> = tokens::Lexer::new(input)
+                .map(|item| item.map(|(token, span)| (token, span.start.offset)))
+                .collect::<Result<_, _>>()
+                .unwrap();
+            assert_eq!(
+                tokens[..tokens.len() - 1],
+                expected[..],
+                "split at {}",
+                split
+            );
+            assert_eq!(tokens.last().unwrap().0, Token::EndOfFile);
+        }
+    }
+
+    #[test]
+    fn byte_at_a_time() {
+        let string = "a\\\"é\\u0041".repeat(20_000);
+        let input = format!("[\"{}\", -12.5e3, /* {} */ true]", string, string);
+        let mut lexer = Lexer::new().comments(true);
+        let mut tokens = Vec::new();
+        for (i, byte) in input.bytes().enumerate() {
+            lexer
+                .feed(&[byte], |token: Token, _| {
+                    tokens.push(token.into_owned());
+                    Ok::<_, Error>(())
+                })
+                .unwrap();
+            // The open string is only scanned as far as it was fed
+            if (3..string.len()).contains(&i) {
+                let open = lexer.open.unwrap();
+                assert_eq!((open.start, open.scanned), (0, lexer.buffer.len()));
+            }
+        }
+        lexer
+            .finish(|token: Token, _| {
+                tokens.push(token.into_owned());
+                Ok::<_, Error>(())
+            })
+            .unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::ArrayStart,
+                Token::String(string.into()),
+                Token::Comma,
+                Token::Number("-12.5e3".into()),
+                Token::Comma,
+                Token::True,
+                Token::ArrayEnd,
+                Token::EndOfFile,
+            ]
+        );
+
+        // Limits still apply before the token ends
+        let mut lexer = Lexer::new().limits(Limits {
+            max_string_bytes: Some(10),
+            ..Limits::default()
+        });
+        let ignore = |_: Token, _: Span| Ok::<_, Error>(());
+        let result = b"[\"aaaaaaaaaaaaaaaa\"]"
+            .iter()
+            .try_for_each(|byte| lexer.feed(&[*byte], ignore));
+        assert!(matches!(
+            result,
+            Err(Error::LimitExceeded(Limit::StringBytes, _))
+        ));
+    }
+
+    #[test]
+    fn line_comment() {
+        let comment = "// a /* b */ é ".repeat(20_000);
+        let input = format!("[1, /* c */ {}\n 2] // {}", comment, comment);
+        let mut lexer = Lexer::new().comments(true);
+        let mut tokens = Vec::new();
+        for (i, chunk) in input.as_bytes().chunks(64).enumerate() {
+            lexer
+                .feed(chunk, |token: Token, _| {
+                    tokens.push(token.into_owned());
+                    Ok::<_, Error>(())
+                })
+                .unwrap();
+            // The open comment is only scanned as far as it was fed
+            if (1..comment.len() / 64).contains(&i) {
+                let open = lexer.open.unwrap();
+                assert!(matches!(open.kind, OpenKind::LineComment));
+                assert_eq!(open.scanned, lexer.buffer.len());
+            }
+        }
+        assert!(matches!(lexer.open.unwrap().kind, OpenKind::LineComment));
+        lexer
+            .finish(|token: Token, _| {
+                tokens.push(token.into_owned());
+                Ok::<_, Error>(())
+            })
+            .unwrap();
+        assert_eq!(
+            tokens,
+            [
+                Token::ArrayStart,
+                Token::Number("1".into()),
+                Token::Comma,
+                Token::Number("2".into()),
+                Token::ArrayEnd,
+                Token::EndOfFile,
+            ]
+        );
+
+        assert_eq!(open_line_comment(b" /* // */ \n// a"), Some(11));
+        assert_eq!(open_line_comment(b"// a\n /* b */ "), None);
+        assert_eq!(open_line_comment(b"/*/ // */"), None);
+    }
+
+    #[test]
+    fn minify_pieces() {
+        let mut minifier = Minifier::new();
+        let mut output = Vec::new();
+        minifier.feed(b"{\"a\": tr", &mut output).unwrap();
+        assert_eq!(output, b"{\"a\":");
+        minifier.feed(b"ue, \"b\": 1", &mut output).unwrap();
+        assert_eq!(output, b"{\"a\":true,\"b\":");
+        minifier.feed(b"0 }  ", &mut output).unwrap();
+        minifier.finish(&mut output).unwrap();
+        assert_eq!(output, b"{\"a\":true,\"b\":10}");
+    }
+
+    #[test]
+    fn truncated() {
+        let finish = |input: &[u8]| {
+            let mut lexer = Lexer::new();
+            let ignore = |_: Token, _: Span| Ok::<_, Error>(());
+            lexer.feed(input, ignore)?;
+            lexer.finish(ignore)
+        };
+        assert!(matches!(
+            finish(b"[\"abc"),
+            Err(Error::UnterminatedString(_))
+        ));
+        assert!(matches!(finish(b"[nul"), Err(Error::UnexpectedEof(_))));
+        assert!(matches!(
+            finish(b"[\"\xc3"),
+            Err(Error::InvalidEncoding(Encoding::Utf8, _))
+        ));
+        assert_eq!(
+            finish(b"[\n\"a\xff\"]"),
+            Err(Error::InvalidEncoding(
+                Encoding::Utf8,
+                Position {
+                    line: 2,
+                    column: 3,
+                    offset: 4
+                }
+            ))
+        );
+
+        let mut minifier = Minifier::new();
+        minifier.feed(b"[1, 2", &mut Vec::new()).unwrap();
+        assert!(minifier.finish(&mut Vec::new()).is_err());
+    }
+}
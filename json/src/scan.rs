@@ -0,0 +1,150 @@
+//! Scanning string bodies several bytes at a time.
+//!
+//! Most of a JSON document is usually string contents that are copied as is,
+//! so instead of looking at one character at a time we look for the first
+//! byte that ends the run: the closing quote, a backslash or a control
+//! character. All of those are ASCII, so the run always ends on a character
+//! boundary.
+
+use std::convert::TryInto;
+
+/// The length of the run at the start of `bytes` that contains no `quote`,
+/// backslash or control character.
+pub(crate) fn string_run(bytes: &[u8], quote: u8) -> usize {
+    let mut i = blocks(bytes, quote);
+    i += words(&bytes[i..], quote);
+    while i < bytes.len() && !is_special(bytes[i], quote) {
+        i += 1;
+    }
+
+    i
+}
+
+fn is_special(byte: u8, quote: u8) -> bool {
+    byte == quote || byte == b'\\' || byte < 0x20
+}
+
+/// Scans 16 bytes at a time with SSE2, which every x86_64 CPU has. Returns
+/// the index of the first special byte, or where the last full block ended.
+#[cfg(target_arch = "x86_64")]
+fn blocks(bytes: &[u8], quote: u8) -> usize {
+    use std::arch::x86_64::*;
+
+    let mut i = 0;
+    // SAFETY: SSE2 is part of the x86_64 baseline and every load reads 16
+    // bytes that are within `bytes`.
+    unsafe {
+        let quote = _mm_set1_epi8(quote as i8);
+        let backslash = _mm_set1_epi8(b'\\' as i8);
+        let control = _mm_set1_epi8(0x1f);
+        while i + 16 <= bytes.len() {
+            let block = _mm_loadu_si128(bytes.as_ptr().add(i) as *const __m128i);
+            // There is no unsigned comparison, but a byte is at most 0x1f if
+            // taking the unsigned maximum with 0x1f leaves 0x1f
+            let special = _mm_or_si128(
+                _mm_or_si128(
+                    _mm_cmpeq_epi8(block, quote),
+                    _mm_cmpeq_epi8(block, backslash),
+                ),
+                _mm_cmpeq_epi8(_mm_max_epu8(block, control), control),
+            );
+            let mask = _mm_movemask_epi8(special);
+            if mask != 0 {
+                return i + mask.trailing_zeros() as usize;
+            }
+            i += 16;
+        }
+    }
+
+    i
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn blocks(_: &[u8], _: u8) -> usize {
+    0
+}
+
+const ONES: u64 = 0x0101_0101_0101_0101;
+const HIGH: u64 = 0x8080_8080_8080_8080;
+
+/// Scans 8 bytes at a time using plain integer arithmetic. Returns the index
+/// of the first special byte, or where the last full word ended.
+fn words(bytes: &[u8], quote: u8) -> usize {
+    let mut i = 0;
+    for word in bytes.chunks_exact(8) {
+        let word = u64::from_le_bytes(word.try_into().unwrap());
+        let special = less_than(word, 0x20)
+            | less_than(word ^ (ONES * u64::from(quote)), 1)
+            | less_than(word ^ (ONES * u64::from(b'\\')), 1);
+        if special != 0 {
+            return i + special.trailing_zeros() as usize / 8;
+        }
+        i += 8;
+    }
+
+    i
+}
+
+/// Sets the high bit of each byte in `word` that is less than `n`, which has
+/// to be at most 128. Bytes after the first one that is set can be wrong
+/// because of borrows, but the first one is always right.
+fn less_than(word: u64, n: u8) -> u64 {
+    word.wrapping_sub(ONES * u64::from(n)) & !word & HIGH
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn naive(bytes: &[u8], quote: u8) -> usize {
+        bytes
+            .iter()
+            .position(|&byte| is_special(byte, quote))
+            .unwrap_or(bytes.len())
+    }
+
+    /// `string_run` without SSE2, so the fallback is tested on x86_64 too.
+    fn words_run(bytes: &[u8], quote: u8) -> usize {
+        let mut i = words(bytes, quote);
+        while i < bytes.len() && !is_special(bytes[i], quote) {
+            i += 1;
+        }
+
+        i
+    }
+
+    #[test]
+    fn special_bytes() {
+        let text = "plain ascii text and some wørds with ünïcødé 🎉 in them, \
+                    followed by a few more bytes to fill several blocks";
+        for &special in &[b'"', b'\'', b'\\', b'\0', b'\n', 0x1f] {
+            for end in 0..text.len() {
+                let mut bytes = text.as_bytes()[..end].to_vec();
+                bytes.push(special);
+                bytes.extend_from_slice(b"tail\"");
+                for &quote in b"\"'" {
+                    assert_eq!(
+                        naive(&bytes, quote),
+                        string_run(&bytes, quote),
+                        "{:?} with {}",
+                        String::from_utf8_lossy(&bytes),
+                        quote as char
+                    );
+                    assert_eq!(naive(&bytes, quote), words_run(&bytes, quote));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn no_special_bytes() {
+        for len in 0..40 {
+            let bytes = vec![b' '; len];
+            assert_eq!(len, string_run(&bytes, b'"'));
+            let bytes = "é".repeat(len);
+            assert_eq!(bytes.len(), string_run(bytes.as_bytes(), b'"'));
+            let bytes = vec![0x7f; len];
+            assert_eq!(len, string_run(&bytes, b'"'));
+        }
+    }
+}
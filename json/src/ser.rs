@@ -0,0 +1,1097 @@
+//! Writing minified JSON straight from serde, without writing the text
+//! first and lexing it again. Enabled by the `serde` feature.
+//!
+//! Values go through the same steps as the input of `Minify`, so the output
+//! options apply to them as well, like `Escapes::Ascii`,
+//! `Options::html_safe`, `Options::precision` or `Options::canonical`.
+//!
+//! ```
+//! use json::{ser, Escapes, Options};
+//! use std::collections::BTreeMap;
+//!
+//! let mut map = BTreeMap::new();
+//! map.insert("name", vec!["été", "<b>"]);
+//! let options = Options {
+//!     escapes: Escapes::Ascii,
+//!     html_safe: true,
+//!     ..Options::default()
+//! };
+//! assert_eq!(
+//!     ser::to_string(&map, options).unwrap(),
+//!     r#"{"name":["\u00e9t\u00e9","\u003cb\u003e"]}"#
+//! );
+//! ```
+
+use crate::error::Position;
+use crate::escape;
+use crate::number;
+use crate::options::Options;
+use crate::pipeline::Pipeline;
+use crate::tokens::Token;
+use serde::de::{self, DeserializeSeed, Deserializer, MapAccess, SeqAccess, Visitor};
+use serde::ser::{self, Impossible, Serialize};
+use std::error;
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+
+/// How much output is collected before it is written, unless a document
+/// ends first.
+const BUFFER_SIZE: usize = 8 * 1024;
+
+#[derive(Debug)]
+pub enum Error {
+    /// Writing the output failed.
+    Io(io::Error),
+    /// The value goes against one of the options, like `Limits` or
+    /// `DuplicateKeys::Error`, or the options conflict.
+    Json(crate::Error),
+    /// An error from the value being serialized or deserialized, or a map
+    /// key that cannot be written as a string.
+    Custom(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::Io(error) => write!(f, "{}", error),
+            Error::Json(error) => write!(f, "{}", error),
+            Error::Custom(message) => f.write_str(message),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Error::Io(error) => Some(error),
+            Error::Json(error) => Some(error),
+            Error::Custom(_) => None,
+        }
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(message: T) -> Self {
+        Error::Custom(message.to_string())
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error::Io(error)
+    }
+}
+
+impl From<crate::Error> for Error {
+    fn from(error: crate::Error) -> Self {
+        Error::Json(error)
+    }
+}
+
+fn key_must_be_a_string() -> Error {
+    Error::Custom("map key must be a string".to_string())
+}
+
+/// Serializes `value` as minified JSON into `writer`.
+pub fn to_writer<W, T>(writer: W, value: &T, options: Options) -> Result<W, Error>
+where
+    W: Write,
+    T: ?Sized + Serialize,
+{
+    let mut serializer = Serializer::with_options(writer, options);
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner())
+}
+
+/// Serializes `value` as minified JSON.
+pub fn to_string<T: ?Sized + Serialize>(value: &T, options: Options) -> Result<String, Error> {
+    let output = to_writer(Vec::new(), value, options)?;
+    // The pipeline only writes valid UTF-8
+    Ok(String::from_utf8(output).unwrap())
+}
+
+/// A serde serializer that writes minified JSON into a writer. Every value
+/// serialized with it is written as a document of its own, right after the
+/// previous one.
+///
+/// The output is buffered, and written whenever a document ends or a few
+/// kilobytes have been collected, so the writer does not need a buffer of
+/// its own.
+pub struct Serializer<W> {
+    writer: W,
+    /// Output not written yet.
+    buffer: String,
+    options: Options,
+    pipeline: Pipeline,
+    /// How many arrays and objects are open.
+    depth: usize,
+    /// Whether the last document is complete, so the next value needs a new
+    /// pipeline.
+    finished: bool,
+}
+
+impl<W: Write> Serializer<W> {
+    pub fn new(writer: W) -> Self {
+        Serializer::with_options(writer, Options::default())
+    }
+
+    pub fn with_options(writer: W, options: Options) -> Self {
+        Serializer {
+            writer,
+            buffer: String::new(),
+            pipeline: Pipeline::new(options.clone()),
+            options,
+            depth: 0,
+            finished: false,
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    fn push(&mut self, token: Token) -> Result<(), Error> {
+        if self.finished {
+            self.pipeline = Pipeline::new(self.options.clone());
+            self.finished = false;
+        }
+        match token {
+            Token::ObjectStart | Token::ArrayStart => self.depth += 1,
+            Token::ObjectEnd | Token::ArrayEnd => self.depth -= 1,
+            _ => {}
+        }
+        let start = matches!(token, Token::ObjectStart | Token::ArrayStart);
+
+        let buffer = &mut self.buffer;
+        let mut write = |token: Token| {
+            // Writing to a string cannot fail
+            write!(buffer, "{}", token).unwrap();
+            Ok::<_, Error>(())
+        };
+        self.pipeline.push(token, Position::default(), &mut write)?;
+        if self.depth == 0 && !start {
+            self.pipeline
+                .push(Token::EndOfFile, Position::default(), &mut write)?;
+            self.finished = true;
+        }
+
+        if self.finished || self.buffer.len() >= BUFFER_SIZE {
+            self.writer.write_all(self.buffer.as_bytes())?;
+            self.buffer.clear();
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, string: &str) -> Result<(), Error> {
+        self.push(Token::String(escape::escape(string)))
+    }
+
+    fn number(&mut self, number: String) -> Result<(), Error> {
+        self.push(Token::Number(number.into()))
+    }
+
+    /// Writes a float from its shortest decimal form, like `1e-7` rather
+    /// than the `0.0000001` it is displayed as. JSON has no infinities or
+    /// NaN, so those are written as `null`.
+    fn float(&mut self, finite: bool, display: String) -> Result<(), Error> {
+        if !finite {
+            return self.push(Token::Null);
+        }
+        let number = number::normalize(display.into(), true).into_owned();
+        self.number(number)
+    }
+
+    /// Starts an object with a single member, how enum variants with data
+    /// are written.
+    fn variant(&mut self, variant: &str) -> Result<(), Error> {
+        self.push(Token::ObjectStart)?;
+        self.string(variant)?;
+        self.push(Token::Colon)
+    }
+}
+
+impl<'a, W: Write> ser::Serializer for &'a mut Serializer<W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Compound<'a, W>;
+    type SerializeTuple = Compound<'a, W>;
+    type SerializeTupleStruct = Compound<'a, W>;
+    type SerializeTupleVariant = Compound<'a, W>;
+    type SerializeMap = Compound<'a, W>;
+    type SerializeStruct = Compound<'a, W>;
+    type SerializeStructVariant = Compound<'a, W>;
+
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        self.push(if value { Token::True } else { Token::False })
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<(), Error> {
+        self.number(value.to_string())
+    }
+
+    fn serialize_f32(self, value: f32) -> Result<(), Error> {
+        self.float(value.is_finite(), value.to_string())
+    }
+
+    fn serialize_f64(self, value: f64) -> Result<(), Error> {
+        self.float(value.is_finite(), value.to_string())
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        self.string(value.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        self.string(value)
+    }
+
+    fn serialize_bytes(self, value: &[u8]) -> Result<(), Error> {
+        use serde::ser::SerializeSeq;
+
+        let mut seq = self.serialize_seq(Some(value.len()))?;
+        for byte in value {
+            seq.serialize_element(byte)?;
+        }
+        seq.end()
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.push(Token::Null)
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.push(Token::Null)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        self.push(Token::Null)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.string(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.variant(variant)?;
+        value.serialize(&mut *self)?;
+        self.push(Token::ObjectEnd)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Compound<'a, W>, Error> {
+        self.push(Token::ArrayStart)?;
+        Ok(Compound::new(self, Token::ArrayEnd))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a, W>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, W>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, W>, Error> {
+        self.variant(variant)?;
+        self.push(Token::ArrayStart)?;
+        Ok(Compound::new(self, Token::ArrayEnd).in_variant())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Compound<'a, W>, Error> {
+        self.push(Token::ObjectStart)?;
+        Ok(Compound::new(self, Token::ObjectEnd))
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<Compound<'a, W>, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a, W>, Error> {
+        self.variant(variant)?;
+        self.push(Token::ObjectStart)?;
+        Ok(Compound::new(self, Token::ObjectEnd).in_variant())
+    }
+}
+
+/// Serializes the elements of an array or the members of an object.
+pub struct Compound<'a, W> {
+    serializer: &'a mut Serializer<W>,
+    /// The token that closes the array or object.
+    end: Token<'static>,
+    /// Whether the array or object is the value of an enum variant, which
+    /// has to be closed as well.
+    variant: bool,
+    first: bool,
+}
+
+impl<'a, W: Write> Compound<'a, W> {
+    fn new(serializer: &'a mut Serializer<W>, end: Token<'static>) -> Self {
+        Compound {
+            serializer,
+            end,
+            variant: false,
+            first: true,
+        }
+    }
+
+    fn in_variant(mut self) -> Self {
+        self.variant = true;
+        self
+    }
+
+    fn separate(&mut self) -> Result<(), Error> {
+        if !self.first {
+            self.serializer.push(Token::Comma)?;
+        }
+        self.first = false;
+        Ok(())
+    }
+
+    fn element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.separate()?;
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn field<T: ?Sized + Serialize>(&mut self, key: &str, value: &T) -> Result<(), Error> {
+        self.separate()?;
+        self.serializer.string(key)?;
+        self.serializer.push(Token::Colon)?;
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn close(self) -> Result<(), Error> {
+        self.serializer.push(self.end)?;
+        if self.variant {
+            self.serializer.push(Token::ObjectEnd)?;
+        }
+        Ok(())
+    }
+}
+
+impl<W: Write> ser::SerializeSeq for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl<W: Write> ser::SerializeTuple for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl<W: Write> ser::SerializeTupleVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        self.element(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl<W: Write> ser::SerializeMap for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: ?Sized + Serialize>(&mut self, key: &T) -> Result<(), Error> {
+        self.separate()?;
+        key.serialize(KeySerializer(&mut *self.serializer))?;
+        self.serializer.push(Token::Colon)
+    }
+
+    fn serialize_value<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(&mut *self.serializer)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl<W: Write> ser::SerializeStruct for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+impl<W: Write> ser::SerializeStructVariant for Compound<'_, W> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: ?Sized + Serialize>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(key, value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.close()
+    }
+}
+
+/// Serializes map keys, which have to be strings in JSON. Like other JSON
+/// serializers, numbers, booleans and characters are accepted and written
+/// as strings.
+struct KeySerializer<'a, W>(&'a mut Serializer<W>);
+
+impl<W: Write> ser::Serializer for KeySerializer<'_, W> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, value: bool) -> Result<(), Error> {
+        self.0.string(if value { "true" } else { "false" })
+    }
+
+    fn serialize_i8(self, value: i8) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_i16(self, value: i16) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_i32(self, value: i32) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_i64(self, value: i64) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_i128(self, value: i128) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_u8(self, value: u8) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_u16(self, value: u16) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_u32(self, value: u32) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_u64(self, value: u64) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_u128(self, value: u128) -> Result<(), Error> {
+        self.0.string(&value.to_string())
+    }
+
+    fn serialize_f32(self, _value: f32) -> Result<(), Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f64(self, _value: f64) -> Result<(), Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_char(self, value: char) -> Result<(), Error> {
+        self.0.string(value.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, value: &str) -> Result<(), Error> {
+        self.0.string(value)
+    }
+
+    fn serialize_bytes(self, _value: &[u8]) -> Result<(), Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: ?Sized + Serialize>(self, _value: &T) -> Result<(), Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.0.string(variant)
+    }
+
+    fn serialize_newtype_struct<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: ?Sized + Serialize>(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(key_must_be_a_string())
+    }
+}
+
+/// Minifies whatever `deserializer` reads into `writer`, like JSON read by
+/// another parser or a document in another self-describing format, without
+/// building the value in memory.
+///
+/// Enums are not supported, since their representation depends on the
+/// format they are read from.
+pub fn transcode<'de, D, W>(deserializer: D, writer: W, options: Options) -> Result<W, Error>
+where
+    D: Deserializer<'de>,
+    W: Write,
+{
+    let mut transcoder = Transcoder {
+        serializer: Serializer::with_options(writer, options),
+        error: None,
+    };
+    match deserializer.deserialize_any(&mut transcoder) {
+        Ok(()) => Ok(transcoder.serializer.into_inner()),
+        // Errors from writing are passed through the deserializer
+        Err(error) => Err(transcoder
+            .error
+            .unwrap_or_else(|| Error::Custom(error.to_string()))),
+    }
+}
+
+/// Writes every value the deserializer visits.
+struct Transcoder<W> {
+    serializer: Serializer<W>,
+    /// The error that stopped the serializer, since it has to be turned into
+    /// an error of the deserializer to stop it.
+    error: Option<Error>,
+}
+
+impl<W: Write> Transcoder<W> {
+    fn check<E: de::Error>(&mut self, result: Result<(), Error>) -> Result<(), E> {
+        result.map_err(|error| {
+            let message = E::custom(&error);
+            self.error = Some(error);
+            message
+        })
+    }
+
+    fn push<E: de::Error>(&mut self, token: Token) -> Result<(), E> {
+        let result = self.serializer.push(token);
+        self.check(result)
+    }
+}
+
+impl<'de, W: Write> Visitor<'de> for &mut Transcoder<W> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("any value that can be written as JSON")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<(), E> {
+        self.push(if value { Token::True } else { Token::False })
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<(), E> {
+        let result = self.serializer.number(value.to_string());
+        self.check(result)
+    }
+
+    fn visit_i128<E: de::Error>(self, value: i128) -> Result<(), E> {
+        let result = self.serializer.number(value.to_string());
+        self.check(result)
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<(), E> {
+        let result = self.serializer.number(value.to_string());
+        self.check(result)
+    }
+
+    fn visit_u128<E: de::Error>(self, value: u128) -> Result<(), E> {
+        let result = self.serializer.number(value.to_string());
+        self.check(result)
+    }
+
+    fn visit_f32<E: de::Error>(self, value: f32) -> Result<(), E> {
+        let result = self.serializer.float(value.is_finite(), value.to_string());
+        self.check(result)
+    }
+
+    fn visit_f64<E: de::Error>(self, value: f64) -> Result<(), E> {
+        let result = self.serializer.float(value.is_finite(), value.to_string());
+        self.check(result)
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<(), E> {
+        let result = self.serializer.string(value);
+        self.check(result)
+    }
+
+    fn visit_bytes<E: de::Error>(self, value: &[u8]) -> Result<(), E> {
+        let result = ser::Serializer::serialize_bytes(&mut self.serializer, value);
+        self.check(result)
+    }
+
+    fn visit_none<E: de::Error>(self) -> Result<(), E> {
+        self.push(Token::Null)
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<(), E> {
+        self.push(Token::Null)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        self.push(Token::ArrayStart)?;
+        let mut comma = false;
+        while seq.next_element_seed(Element(&mut *self, comma))?.is_some() {
+            comma = true;
+        }
+        self.push(Token::ArrayEnd)
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        self.push(Token::ObjectStart)?;
+        let mut comma = false;
+        while map.next_key_seed(Key(&mut *self, comma))?.is_some() {
+            map.next_value_seed(Element(&mut *self, false))?;
+            comma = true;
+        }
+        self.push(Token::ObjectEnd)
+    }
+}
+
+/// An element of an array or the value of an object member, with a comma
+/// before it unless it is the first.
+struct Element<'a, W>(&'a mut Transcoder<W>, bool);
+
+impl<'de, W: Write> DeserializeSeed<'de> for Element<'_, W> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        let Element(transcoder, comma) = self;
+        if comma {
+            transcoder.push(Token::Comma)?;
+        }
+        deserializer.deserialize_any(transcoder)
+    }
+}
+
+/// The key of an object member and the colon after it, with a comma before
+/// it unless it is the first.
+struct Key<'a, W>(&'a mut Transcoder<W>, bool);
+
+impl<'de, W: Write> DeserializeSeed<'de> for Key<'_, W> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        let Key(transcoder, comma) = self;
+        if comma {
+            transcoder.push(Token::Comma)?;
+        }
+        deserializer.deserialize_any(KeyVisitor(&mut *transcoder))?;
+        transcoder.push(Token::Colon)
+    }
+}
+
+/// Writes a key as a string, also when it was read as a number or a
+/// boolean, like the keys of a YAML map can be.
+struct KeyVisitor<'a, W>(&'a mut Transcoder<W>);
+
+impl<W: Write> KeyVisitor<'_, W> {
+    fn key<E: de::Error>(self, key: &str) -> Result<(), E> {
+        let result = self.0.serializer.string(key);
+        self.0.check(result)
+    }
+}
+
+impl<'de, W: Write> Visitor<'de> for KeyVisitor<'_, W> {
+    type Value = ();
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("a string key")
+    }
+
+    fn visit_bool<E: de::Error>(self, value: bool) -> Result<(), E> {
+        self.key(if value { "true" } else { "false" })
+    }
+
+    fn visit_i64<E: de::Error>(self, value: i64) -> Result<(), E> {
+        self.key(&value.to_string())
+    }
+
+    fn visit_u64<E: de::Error>(self, value: u64) -> Result<(), E> {
+        self.key(&value.to_string())
+    }
+
+    fn visit_str<E: de::Error>(self, value: &str) -> Result<(), E> {
+        self.key(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::Limit;
+    use crate::options::{Escapes, Limits, Precision};
+    use std::collections::{BTreeMap, HashMap};
+
+    #[test]
+    fn values() {
+        let mut map = BTreeMap::new();
+        map.insert("b", vec![None, Some(1.5), Some(f64::NAN), Some(1e-7)]);
+        map.insert("a\n\"", vec![Some(-0.0), Some(1e300)]);
+        assert_eq!(
+            to_string(&map, Options::default()).unwrap(),
+            r#"{"a\n\"":[-0,1e300],"b":[null,1.5,null,1e-7]}"#
+        );
+
+        let value = (
+            'x',
+            u128::MAX,
+            0.1f32,
+            [Ok::<_, ()>(()), Err(())],
+            BTreeMap::from([(1, true), (2, false)]),
+        );
+        assert_eq!(
+            to_string(&value, Options::default()).unwrap(),
+            r#"["x",340282366920938463463374607431768211455,0.1,[{"Ok":null},{"Err":null}],{"1":true,"2":false}]"#
+        );
+
+        let tuple_keys = BTreeMap::from([((1, 2), 3)]);
+        assert!(matches!(
+            to_string(&tuple_keys, Options::default()),
+            Err(Error::Custom(_))
+        ));
+
+        // Every value is a document of its own
+        let mut serializer = Serializer::new(Vec::new());
+        [1, 2].serialize(&mut serializer).unwrap();
+        "a".serialize(&mut serializer).unwrap();
+        assert_eq!(serializer.into_inner(), b"[1,2]\"a\"");
+    }
+
+    #[test]
+    fn options() {
+        let map = HashMap::from([("z", Some("<é>")), ("a", None), ("m", Some("x"))]);
+        let options = Options {
+            canonical: true,
+            drop_nulls: true,
+            ..Options::default()
+        };
+        assert_eq!(to_string(&map, options).unwrap(), r#"{"m":"x","z":"<é>"}"#);
+
+        let options = Options {
+            escapes: Escapes::Ascii,
+            html_safe: true,
+            ..Options::default()
+        };
+        assert_eq!(
+            to_string(&["<é>"], options).unwrap(),
+            r#"["\u003c\u00e9\u003e"]"#
+        );
+
+        let options = Options {
+            precision: Some(Precision::DecimalPlaces(2)),
+            ..Options::default()
+        };
+        assert_eq!(to_string(&[1.23456, 2.0], options).unwrap(), "[1.23,2]");
+
+        let options = Options {
+            limits: Limits {
+                max_depth: Some(1),
+                ..Limits::default()
+            },
+            ..Options::default()
+        };
+        assert!(matches!(
+            to_string(&[[1]], options),
+            Err(Error::Json(crate::Error::LimitExceeded(Limit::Depth, _)))
+        ));
+
+        let options = Options {
+            canonical: true,
+            escapes: Escapes::Ascii,
+            ..Options::default()
+        };
+        assert!(matches!(
+            to_string(&1, options),
+            Err(Error::Json(crate::Error::ConflictingOptions(..)))
+        ));
+    }
+
+    /// Counts the writes and fails once `fail` of them are done.
+    struct Writes {
+        output: Vec<u8>,
+        writes: usize,
+        fail: usize,
+    }
+
+    impl Write for Writes {
+        fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+            if self.writes == self.fail {
+                return Err(io::ErrorKind::BrokenPipe.into());
+            }
+            self.writes += 1;
+            self.output.extend_from_slice(bytes);
+            Ok(bytes.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn writing() {
+        let writer = Writes {
+            output: Vec::new(),
+            writes: 0,
+            fail: usize::MAX,
+        };
+        let mut serializer = Serializer::new(writer);
+        let map = BTreeMap::from([("a", vec![1, 2, 3]), ("b", vec![])]);
+        map.serialize(&mut serializer).unwrap();
+        "x".serialize(&mut serializer).unwrap();
+        let writer = serializer.into_inner();
+        assert_eq!(writer.output, br#"{"a":[1,2,3],"b":[]}"x""#);
+        // One write per document
+        assert_eq!(writer.writes, 2);
+
+        // Large documents are written in parts
+        let writer = Writes {
+            output: Vec::new(),
+            writes: 0,
+            fail: usize::MAX,
+        };
+        let strings = vec!["a".repeat(1000); 20];
+        let writer = to_writer(writer, &strings, Options::default()).unwrap();
+        assert!(writer.writes > 1);
+        assert_eq!(
+            writer.output,
+            to_string(&strings, Options::default()).unwrap().as_bytes()
+        );
+
+        let writer = Writes {
+            output: Vec::new(),
+            writes: 0,
+            fail: 0,
+        };
+        assert!(matches!(
+            to_writer(writer, &[1], Options::default()),
+            Err(Error::Io(_))
+        ));
+    }
+
+    #[test]
+    fn transcoding() {
+        let source = r#"{"a": [1, 2.50, "xé"], "b": {"c": null}, "d": -0}"#;
+        let options = Options {
+            drop_nulls: true,
+            escapes: Escapes::Ascii,
+            ..Options::default()
+        };
+        let mut deserializer = serde_json::Deserializer::from_str(source);
+        let output = transcode(&mut deserializer, Vec::new(), options).unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            r#"{"a":[1,2.5,"x\u00e9"],"b":{},"d":-0}"#
+        );
+
+        let mut deserializer = serde_json::Deserializer::from_str("[1, 2");
+        let error = transcode(&mut deserializer, Vec::new(), Options::default()).unwrap_err();
+        assert!(matches!(error, Error::Custom(_)));
+    }
+}
@@ -0,0 +1,283 @@
+use crate::duplicates::Duplicate;
+use crate::options::Options;
+use crate::push;
+use std::io::{self, Read, Write};
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Minifies JSON from a reader into a writer without loading the whole
+/// document into memory.
+///
+/// Input is read in fixed size chunks and every complete token is written
+/// out as soon as it has been lexed, so memory use is bounded by the chunk
+/// size and the largest single token in the document.
+pub struct Minifier<R, W> {
+    reader: R,
+    writer: W,
+    chunk_size: usize,
+    minifier: push::Minifier,
+    on_duplicate: Option<Box<dyn FnMut(Duplicate)>>,
+}
+
+impl<R: Read, W: Write> Minifier<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        Minifier::with_chunk_size(reader, writer, CHUNK_SIZE)
+    }
+
+    pub fn with_chunk_size(reader: R, writer: W, chunk_size: usize) -> Self {
+        Minifier {
+            reader,
+            writer,
+            chunk_size: chunk_size.max(1),
+            minifier: push::Minifier::new(),
+            on_duplicate: None,
+        }
+    }
+
+    pub fn options(mut self, options: Options) -> Self {
+        self.minifier = push::Minifier::with_options(options);
+        self
+    }
+
+    /// Calls `report` with every duplicate key found, unless
+    /// `Options::duplicate_keys` allows them.
+    pub fn on_duplicate<F: FnMut(Duplicate) + 'static>(mut self, report: F) -> Self {
+        self.on_duplicate = Some(Box::new(report));
+        self
+    }
+
+    /// Minifies the whole input and returns the writer.
+    pub fn minify(mut self) -> io::Result<W> {
+        let mut chunk = vec![0; self.chunk_size];
+        loop {
+            let read = match self.reader.read(&mut chunk) {
+                Ok(read) => read,
+                Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                Err(e) => return Err(e),
+            };
+            if read == 0 {
+                self.minifier.finish(&mut self.writer)?;
+            } else {
+                self.minifier.feed(&chunk[..read], &mut self.writer)?;
+            }
+            if let Some(report) = &mut self.on_duplicate {
+                self.minifier.take_duplicates().into_iter().for_each(report);
+            }
+            if read == 0 {
+                break;
+            }
+        }
+
+        self.writer.flush()?;
+        Ok(self.writer)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::error::{Error, Position};
+
+    fn minify(input: &str, chunk_size: usize) -> String {
+        let output = Minifier::with_chunk_size(input.as_bytes(), Vec::new(), chunk_size)
+            .minify()
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn small() {
+        let input = r#"{
+  "a": "b",
+  "c": "d"
+}"#;
+        assert_eq!(r#"{"a":"b","c":"d"}"#, minify(input, CHUNK_SIZE));
+    }
+
+    #[test]
+    fn chunk_boundaries() {
+        let input = r#"{
+  "name": "ola nordmann",
+  "age": 100,
+  "alive": true,
+  "spouse": null,
+  "messages": ["hello", "wørld", "!"]
+}"#;
+        let expected = r#"{"name":"ola nordmann","age":100,"alive":true,"spouse":null,"messages":["hello","wørld","!"]}"#;
+        for chunk_size in 1..input.len() {
+            assert_eq!(expected, minify(input, chunk_size));
+        }
+    }
+
+    #[test]
+    fn grammar() {
+        let input = "[1, 2,]";
+        let error = Minifier::new(input.as_bytes(), Vec::new())
+            .minify()
+            .unwrap_err();
+        let error = error.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert!(matches!(*error, Error::UnexpectedToken("']'", _)));
+
+        let lenient = Options {
+            lenient: true,
+            ..Options::default()
+        };
+        let output = Minifier::with_chunk_size(input.as_bytes(), Vec::new(), 2)
+            .options(lenient)
+            .minify()
+            .unwrap();
+        assert_eq!(b"[1,2,]", output.as_slice());
+    }
+
+    #[test]
+    fn comments() {
+        let input = "// settings\n{\n  \"a\": 1, /* the\n best */ \"b\": [2] // end\n}\n";
+        let comments = Options {
+            comments: true,
+            ..Options::default()
+        };
+        for chunk_size in 1..input.len() {
+            let output = Minifier::with_chunk_size(input.as_bytes(), Vec::new(), chunk_size)
+                .options(comments.clone())
+                .minify()
+                .unwrap();
+            assert_eq!(br#"{"a":1,"b":[2]}"#, output.as_slice());
+        }
+    }
+
+    #[test]
+    fn json5() {
+        let input = "{a: 'b', c: [0x10, .5, +1,], // comment\n d: 'e\\\nf',}";
+        let json5 = Options {
+            json5: true,
+            ..Options::default()
+        };
+        for chunk_size in 1..input.len() {
+            let output = Minifier::with_chunk_size(input.as_bytes(), Vec::new(), chunk_size)
+                .options(json5.clone())
+                .minify()
+                .unwrap();
+            assert_eq!(br#"{"a":"b","c":[16,0.5,1],"d":"ef"}"#, output.as_slice());
+        }
+    }
+
+    #[test]
+    fn canonical() {
+        let input = "{\"b\": [1.0, \"\\u0041\"], \"a\": 1e2}";
+        let canonical = Options {
+            canonical: true,
+            ..Options::default()
+        };
+        for chunk_size in 1..input.len() {
+            let output = Minifier::with_chunk_size(input.as_bytes(), Vec::new(), chunk_size)
+                .options(canonical.clone())
+                .minify()
+                .unwrap();
+            assert_eq!(br#"{"a":100,"b":[1,"A"]}"#, output.as_slice());
+        }
+    }
+
+    #[test]
+    fn duplicate_keys() {
+        use crate::options::DuplicateKeys;
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let input = r#"{"a": "first", "b": {"a": 1}, "a": "last"}"#;
+        let keep_last = Options {
+            duplicate_keys: DuplicateKeys::KeepLast,
+            ..Options::default()
+        };
+        for chunk_size in 1..input.len() {
+            let found = Rc::new(Cell::new(0));
+            let counter = Rc::clone(&found);
+            let output = Minifier::with_chunk_size(input.as_bytes(), Vec::new(), chunk_size)
+                .options(keep_last.clone())
+                .on_duplicate(move |duplicate| {
+                    assert_eq!(duplicate.key, "a");
+                    counter.set(counter.get() + 1);
+                })
+                .minify()
+                .unwrap();
+            assert_eq!(br#"{"b":{"a":1},"a":"last"}"#, output.as_slice());
+            assert_eq!(found.get(), 1);
+        }
+    }
+
+    #[test]
+    fn filters() {
+        let input = r#"{"a": null, "b": {"c": []}, "d": [1, {"e": null}]}"#;
+        let options = Options {
+            drop_nulls: true,
+            drop_empty: true,
+            ..Options::default()
+        };
+        for chunk_size in 1..input.len() {
+            let output = Minifier::with_chunk_size(input.as_bytes(), Vec::new(), chunk_size)
+                .options(options.clone())
+                .minify()
+                .unwrap();
+            assert_eq!(br#"{"d":[1,{}]}"#, output.as_slice());
+        }
+    }
+
+    #[test]
+    fn limits() {
+        use crate::error::Limit;
+        use crate::options::Limits;
+
+        // Both would be read forever without the limits
+        let endless_string = b"[\"".chain(io::repeat(b'a'));
+        let endless_whitespace = b"[".chain(io::repeat(b' '));
+        let limits = Options {
+            limits: Limits {
+                max_string_bytes: Some(100),
+                max_input_bytes: Some(1000),
+                ..Limits::default()
+            },
+            ..Options::default()
+        };
+        for (reader, limit) in [
+            (
+                Box::new(endless_string) as Box<dyn Read>,
+                Limit::StringBytes,
+            ),
+            (Box::new(endless_whitespace), Limit::InputBytes),
+        ] {
+            let error = Minifier::with_chunk_size(reader, Vec::new(), 64)
+                .options(limits.clone())
+                .minify()
+                .unwrap_err();
+            let error = error.into_inner().unwrap().downcast::<Error>().unwrap();
+            assert!(matches!(*error, Error::LimitExceeded(exceeded, _) if exceeded == limit));
+        }
+    }
+
+    #[test]
+    fn invalid_utf8() {
+        let input: &[u8] = b"[\"\xff\"]";
+        let result = Minifier::new(input, Vec::new()).minify();
+        assert_eq!(result.unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn parsing_error() {
+        let input = "[\n  1,\n  ?\n]";
+        let error = Minifier::with_chunk_size(input.as_bytes(), Vec::new(), 4)
+            .minify()
+            .unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+        let error = error.into_inner().unwrap().downcast::<Error>().unwrap();
+        assert_eq!(
+            *error,
+            Error::UnexpectedCharacter(
+                '?',
+                Position {
+                    line: 3,
+                    column: 3,
+                    offset: 9
+                }
+            )
+        );
+    }
+}
@@ -1,10 +1,37 @@
-use std::iter::Peekable;
-use std::str::Chars;
+//! The JSON lexer, for tools that want to walk the tokens of a document
+//! themselves.
+//!
+//! ```
+//! use json::tokens::{Lexer, Token};
+//!
+//! let tokens: Vec<_> = Lexer::new(r#"{"a": 1}"#)
+//!     .map(|token| token.map(|(token, _)| token))
+//!     .collect::<Result<_, _>>()
+//!     .unwrap();
+//! assert_eq!(tokens[1], Token::String("a".into()));
+//! assert_eq!(tokens.len(), 5);
+//! ```
 
+use crate::error::{Error, Limit, Position};
+use crate::escape;
+use crate::options::Limits;
+use crate::scan;
+use std::borrow::Cow;
+use std::fmt;
+
+/// A token, strings and numbers are borrowed from the input as written,
+/// without their quotes, unless they have been rewritten.
 #[derive(Debug, PartialEq, Clone)]
-pub enum Token {
-    String(String),
-    Number(String),
+pub enum Token<'a> {
+    /// A string in double quotes, with its escapes as written unless the
+    /// lexer decodes them.
+    String(Cow<'a, str>),
+    /// A JSON5 string in single quotes.
+    SingleQuoted(Cow<'a, str>),
+    /// A JSON5 unquoted object key.
+    Identifier(Cow<'a, str>),
+    /// A number as written.
+    Number(Cow<'a, str>),
     Null,
     True,
     False,
@@ -14,131 +41,671 @@ pub enum Token {
     ObjectEnd,
     ArrayStart,
     ArrayEnd,
+    /// The end of the input, returned by `Lexer::next_token` but never by
+    /// the iterator.
     EndOfFile,
-    Error,
 }
 
+/// Where a token is in the input, from its first character up to the
+/// character after it.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Span {
+    pub start: Position,
+    pub end: Position,
+}
+
+impl<'a> Token<'a> {
+    /// A short description of the token for error messages.
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Token::String(_) | Token::SingleQuoted(_) => "string",
+            Token::Identifier(_) => "identifier",
+            Token::Number(_) => "number",
+            Token::Null => "null",
+            Token::True => "true",
+            Token::False => "false",
+            Token::Comma => "','",
+            Token::Colon => "':'",
+            Token::ObjectStart => "'{'",
+            Token::ObjectEnd => "'}'",
+            Token::ArrayStart => "'['",
+            Token::ArrayEnd => "']'",
+            Token::EndOfFile => "end of input",
+        }
+    }
+
+    /// Copies any borrowed text, so the token can outlive the input.
+    pub fn into_owned(self) -> Token<'static> {
+        let owned = |text: Cow<str>| Cow::Owned(text.into_owned());
+        match self {
+            Token::String(string) => Token::String(owned(string)),
+            Token::SingleQuoted(string) => Token::SingleQuoted(owned(string)),
+            Token::Identifier(name) => Token::Identifier(owned(name)),
+            Token::Number(number) => Token::Number(owned(number)),
+            Token::Null => Token::Null,
+            Token::True => Token::True,
+            Token::False => Token::False,
+            Token::Comma => Token::Comma,
+            Token::Colon => Token::Colon,
+            Token::ObjectStart => Token::ObjectStart,
+            Token::ObjectEnd => Token::ObjectEnd,
+            Token::ArrayStart => Token::ArrayStart,
+            Token::ArrayEnd => Token::ArrayEnd,
+            Token::EndOfFile => Token::EndOfFile,
+        }
+    }
+}
+
+impl<'a> fmt::Display for Token<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::String(string) => write!(f, "\"{}\"", string),
+            Token::SingleQuoted(string) => write!(f, "'{}'", string),
+            Token::Identifier(name) => f.write_str(name),
+            Token::Number(number) => f.write_str(number),
+            Token::Null => f.write_str("null"),
+            Token::True => f.write_str("true"),
+            Token::False => f.write_str("false"),
+            Token::Comma => f.write_str(","),
+            Token::Colon => f.write_str(":"),
+            Token::ObjectStart => f.write_str("{"),
+            Token::ObjectEnd => f.write_str("}"),
+            Token::ArrayStart => f.write_str("["),
+            Token::ArrayEnd => f.write_str("]"),
+            Token::EndOfFile => Ok(()),
+        }
+    }
+}
+
+/// What the lexer accepts and how it reads strings, see the `Lexer` methods
+/// of the same names.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Settings {
+    pub comments: bool,
+    pub json5: bool,
+    pub decode: bool,
+    pub limits: Limits,
+}
+
+/// Splits JSON into tokens. As an iterator it returns every token with its
+/// span, and stops after the end of the input or the first error.
 pub struct Lexer<'a> {
-    input: Peekable<Chars<'a>>,
+    source: &'a str,
+    /// Offset of `source` in the document.
+    base: usize,
+    position: Position,
+    start: Position,
+    exhausted: bool,
+    settings: Settings,
+    /// Whether the iterator has returned its last item.
+    done: bool,
 }
 
 impl<'a> Lexer<'a> {
     pub fn new(input: &'a str) -> Self {
         Lexer {
-            input: input.chars().peekable(),
+            source: input,
+            base: 0,
+            position: Position::default(),
+            start: Position::default(),
+            exhausted: false,
+            settings: Settings::default(),
+            done: false,
         }
     }
 
+    /// Sets where the input starts in a larger document, so reported
+    /// positions are relative to the whole document.
+    pub fn starting_at(mut self, position: Position) -> Self {
+        self.position = position;
+        self.start = position;
+        self.base = position.offset;
+        self
+    }
+
+    /// Whether `//` and `/* */` comments are skipped like whitespace.
+    pub fn comments(mut self, comments: bool) -> Self {
+        self.settings.comments = comments;
+        self
+    }
+
+    /// Whether to accept JSON5 instead of JSON, this includes comments.
+    pub fn json5(mut self, json5: bool) -> Self {
+        self.settings.json5 = json5;
+        self
+    }
+
+    /// Whether to decode every escape in strings that is not needed, so
+    /// strings only escape `"`, `\` and control characters. Strings without
+    /// escapes are still borrowed from the input.
+    pub fn decode_escapes(mut self, decode: bool) -> Self {
+        self.settings.decode = decode;
+        self
+    }
+
+    /// Limits on the length of strings and numbers and on the size of the
+    /// input. The other limits are checked by the minifiers.
+    pub fn limits(mut self, limits: Limits) -> Self {
+        self.settings.limits = limits;
+        self
+    }
+
+    pub(crate) fn with_settings(mut self, settings: Settings) -> Self {
+        self.settings = settings;
+        self
+    }
+
+    /// Position of the next character to be read.
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Position where the last token returned by `next_token` started.
+    pub fn start(&self) -> Position {
+        self.start
+    }
+
+    /// Whether the last call to `next_token` ran into the end of the input,
+    /// meaning the token might continue if more input was available.
+    pub(crate) fn reached_end(&self) -> bool {
+        self.exhausted
+    }
+
+    /// The input that has not been read yet.
+    fn rest(&self) -> &'a str {
+        &self.source[self.position.offset - self.base..]
+    }
+
     fn read(&mut self) -> Option<char> {
-        self.input.next()
+        let c = match self.rest().as_bytes().first() {
+            Some(&byte) if byte.is_ascii() => byte as char,
+            Some(_) => self.rest().chars().next().unwrap(),
+            None => {
+                self.exhausted = true;
+                return None;
+            }
+        };
+        self.position.advance(c);
+        Some(c)
     }
 
-    fn peek(&mut self) -> Option<&char> {
-        self.input.peek()
+    /// Skips `run`, which is the start of the unread input and has no line
+    /// breaks.
+    fn skip_run(&mut self, run: &str) {
+        self.position.offset += run.len();
+        self.position.column += run.chars().count();
     }
 
-    fn skip_whitespace(&mut self) {
-        while let Some(&c) = self.peek() {
-            if !c.is_whitespace() {
-                break;
+    /// The input from `start` up to the next character to be read.
+    fn slice(&self, start: Position) -> &'a str {
+        &self.source[start.offset - self.base..self.position.offset - self.base]
+    }
+
+    /// The next byte, which is the start of the next character.
+    fn peek(&mut self) -> Option<u8> {
+        let next = self.rest().as_bytes().first().copied();
+        if next.is_none() {
+            self.exhausted = true;
+        }
+        next
+    }
+
+    fn peek_char(&mut self) -> Option<char> {
+        let next = self.rest().chars().next();
+        if next.is_none() {
+            self.exhausted = true;
+        }
+        next
+    }
+
+    fn skip_whitespace(&mut self) -> Result<(), Error> {
+        loop {
+            match self.peek() {
+                Some(b' ') | Some(b'\t') | Some(b'\n') | Some(b'\r') => {
+                    self.read();
+                }
+                Some(b'/') if self.settings.comments || self.settings.json5 => {
+                    self.skip_comment()?
+                }
+                // JSON5 also allows the rest of the ECMAScript whitespace
+                Some(_) if self.settings.json5 => match self.peek_char() {
+                    Some(c) if c.is_whitespace() || c == '\u{feff}' => {
+                        self.read();
+                    }
+                    _ => break,
+                },
+                _ => break,
             }
-            self.read();
         }
+
+        Ok(())
     }
 
-    fn read_ident(&mut self, target: &str) -> bool {
-        let mut clone = self.input.clone();
-        let len = target.len();
-        for c in target.chars() {
-            if clone.next() == Some(c) {
-                continue;
-            } else {
-                return false;
+    /// Skips a `// line` or `/* block */` comment.
+    fn skip_comment(&mut self) -> Result<(), Error> {
+        let start = self.position;
+        // So errors in the comment have it as their token
+        self.start = start;
+        self.read();
+        match self.read() {
+            Some('/') => while self.read().is_some_and(|c| c != '\n') {},
+            Some('*') => {
+                let mut star = false;
+                loop {
+                    match self.read() {
+                        Some('/') if star => break,
+                        Some(c) => star = c == '*',
+                        None => return Err(Error::UnterminatedComment(start)),
+                    }
+                }
             }
+            _ => return Err(Error::UnexpectedCharacter('/', start)),
         }
 
-        for _ in 0..len {
-            self.read();
+        Ok(())
+    }
+
+    /// Reads the rest of a `null`, `true` or `false` literal.
+    fn read_literal(&mut self, rest: &str, token: Token<'a>) -> Result<Token<'a>, Error> {
+        for expected in rest.chars() {
+            let position = self.position;
+            match self.read() {
+                Some(c) if c == expected => continue,
+                Some(c) => return Err(Error::UnexpectedCharacter(c, position)),
+                None => return Err(Error::UnexpectedEof(position)),
+            }
         }
 
-        true
+        Ok(token)
     }
 
-    fn read_string(&mut self, init: char) -> Token {
-        let mut ident = String::new();
-        let mut slash = false;
-        ident.push(init);
-        while let Some(c) = self.peek() {
-            if c == &'"' && !slash {
-                break;
+    /// Reads the body of a string, the opening `quote` at `start` has already
+    /// been read. The string is kept as written, escapes are validated but
+    /// only decoded if `decode` is set.
+    fn read_string(&mut self, quote: char, start: Position) -> Result<Cow<'a, str>, Error> {
+        let mut decoded: Option<String> = None;
+        loop {
+            // Skip ahead to the next character that needs a closer look
+            let rest = self.rest();
+            let run = &rest[..scan::string_run(rest.as_bytes(), quote as u8)];
+            self.skip_run(run);
+            if let Some(decoded) = &mut decoded {
+                decoded.push_str(run);
+            }
+
+            let position = self.position;
+            match self.read() {
+                Some(c) if c == quote => {
+                    return Ok(match decoded {
+                        Some(decoded) => decoded.into(),
+                        None => {
+                            let string = self.slice(start);
+                            string[1..string.len() - 1].into()
+                        }
+                    });
+                }
+                Some('\\') => {
+                    let c = self.read_escape(start, position)?;
+                    if self.settings.decode {
+                        let decoded = decoded.get_or_insert_with(|| {
+                            let string = &self.source[..position.offset - self.base];
+                            string[start.offset - self.base + 1..].to_string()
+                        });
+                        if let Some(c) = c {
+                            escape::push_escaped(decoded, c, quote);
+                        }
+                    }
+                }
+                // JSON5 allows most control characters, but not line breaks
+                Some('\n') | Some('\r') if self.settings.json5 => {
+                    return Err(Error::ControlCharacter(position))
+                }
+                Some(c) if c < '\u{20}' && !self.settings.json5 => {
+                    return Err(Error::ControlCharacter(position))
+                }
+                Some(c) => {
+                    if let Some(decoded) = &mut decoded {
+                        decoded.push(c);
+                    }
+                }
+                // Give up on strings that are already too long instead of
+                // waiting for more input
+                None if exceeds(
+                    self.settings.limits.max_string_bytes,
+                    position.offset - start.offset - 1,
+                ) =>
+                {
+                    return Err(Error::LimitExceeded(Limit::StringBytes, start))
+                }
+                None => return Err(Error::UnterminatedString(start)),
+            }
+        }
+    }
+
+    /// Reads an escape sequence starting at `escape`, the backslash has
+    /// already been read, and returns the character it stands for. JSON5
+    /// line continuations do not stand for any character.
+    fn read_escape(&mut self, start: Position, escape: Position) -> Result<Option<char>, Error> {
+        let c = match self.read() {
+            Some('b') => '\u{8}',
+            Some('f') => '\u{c}',
+            Some('n') => '\n',
+            Some('r') => '\r',
+            Some('t') => '\t',
+            Some(c) if "\"\\/".contains(c) => c,
+            Some('u') => {
+                let unit = match self.read_hex(4, start, escape)? {
+                    // A high surrogate has to be followed by an escaped low surrogate
+                    high @ 0xD800..=0xDBFF => {
+                        let low = self.position;
+                        match (self.read(), self.read()) {
+                            (Some('\\'), Some('u')) => {}
+                            (None, _) | (Some('\\'), None) => {
+                                return Err(Error::UnterminatedString(start))
+                            }
+                            _ => return Err(Error::UnpairedSurrogate(escape)),
+                        }
+                        match self.read_hex(4, start, low)? {
+                            low @ 0xDC00..=0xDFFF => {
+                                0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+                            }
+                            _ => return Err(Error::UnpairedSurrogate(escape)),
+                        }
+                    }
+                    0xDC00..=0xDFFF => return Err(Error::UnpairedSurrogate(escape)),
+                    unit => unit,
+                };
+                std::char::from_u32(unit).unwrap()
             }
-            if c == &'\\' {
-                slash = true;
-                ident.push('\\');
+            Some('x') if self.settings.json5 => {
+                std::char::from_u32(self.read_hex(2, start, escape)?).unwrap()
+            }
+            // `\0` is allowed, but not when it looks like an octal escape
+            Some('0') if self.settings.json5 => {
+                if self.peek().is_some_and(|byte| byte.is_ascii_digit()) {
+                    return Err(Error::InvalidEscape(escape));
+                }
+                '\0'
+            }
+            Some('v') if self.settings.json5 => '\u{b}',
+            // An escaped line break continues the string on the next line,
+            // `\r\n` counts as one line break
+            Some('\r') if self.settings.json5 => {
+                if self.peek() == Some(b'\n') {
+                    self.read();
+                }
+                return Ok(None);
+            }
+            Some('\n') | Some('\u{2028}') | Some('\u{2029}') if self.settings.json5 => {
+                return Ok(None)
+            }
+            Some(c) if self.settings.json5 && !c.is_ascii_digit() => c,
+            Some(_) => return Err(Error::InvalidEscape(escape)),
+            None => return Err(Error::UnterminatedString(start)),
+        };
+
+        Ok(Some(c))
+    }
+
+    fn read_hex(&mut self, digits: usize, start: Position, escape: Position) -> Result<u32, Error> {
+        let mut unit = 0;
+        for _ in 0..digits {
+            let c = self.read().ok_or(Error::UnterminatedString(start))?;
+            let digit = c.to_digit(16).ok_or(Error::InvalidEscape(escape))?;
+            unit = unit * 16 + digit;
+        }
+
+        Ok(unit)
+    }
+
+    /// Reads a number following the JSON grammar, the first character at
+    /// `start`, a digit or `-`, has already been read.
+    fn read_number(&mut self, start: Position) -> Result<Token<'a>, Error> {
+        let invalid = Err(Error::InvalidNumber(start));
+        self.read_digits();
+        let integer = self.slice(start).trim_start_matches('-');
+        // Zero is the only integer part that can start with a zero
+        if integer.is_empty() || (integer.len() > 1 && integer.starts_with('0')) {
+            return invalid;
+        }
+        if self.peek() == Some(b'.') {
+            self.read();
+            if self.read_digits() == 0 {
+                return invalid;
+            }
+        }
+        if let Some(b'e') | Some(b'E') = self.peek() {
+            self.read();
+            if let Some(b'+') | Some(b'-') = self.peek() {
                 self.read();
-                continue;
             }
-            if slash && c == &'"' {
-                slash = false;
+            if self.read_digits() == 0 {
+                return invalid;
             }
-            ident.push(self.read().expect("Could not parse ident"));
         }
-        self.read();
-        Token::String(ident)
+
+        // Reject numbers that run straight into more number-like text, like
+        // `1.2.3` or `12abc`, instead of splitting them into several tokens
+        if self
+            .peek()
+            .is_some_and(|byte| byte.is_ascii_alphanumeric() || b"+-.".contains(&byte))
+        {
+            return invalid;
+        }
+
+        Ok(Token::Number(self.slice(start).into()))
+    }
+
+    /// Reads a run of decimal digits and returns how many there were.
+    fn read_digits(&mut self) -> usize {
+        let mut digits = 0;
+        while let Some(b'0'..=b'9') = self.peek() {
+            self.read();
+            digits += 1;
+        }
+
+        digits
     }
 
-    fn read_number(&mut self, init: char) -> Token {
-        let mut number = String::new();
-        number.push(init);
-        while let Some(c) = self.peek() {
-            if c == &',' || c == &']' || c == &'}' || c.is_whitespace() {
+    /// Reads a JSON5 number, which can also be hexadecimal, have a leading
+    /// `+` or a leading or trailing decimal point, or be `Infinity` or `NaN`.
+    fn read_json5_number(&mut self, start: Position) -> Result<Token<'a>, Error> {
+        while let Some(byte) = self.peek() {
+            if !(byte.is_ascii_alphanumeric() || b"+-.".contains(&byte)) {
                 break;
             }
-            number.push(self.read().expect("Could not parse number"));
+            self.read();
+        }
+
+        let number = self.slice(start);
+        if !is_json5_number(number) {
+            return Err(Error::InvalidNumber(start));
         }
-        Token::Number(number)
+
+        Ok(Token::Number(number.into()))
     }
 
-    fn next_token(&mut self) -> Token {
-        self.skip_whitespace();
+    /// Reads a JSON5 identifier, or one of the keywords.
+    fn read_word(&mut self, init: char, start: Position) -> Result<Token<'a>, Error> {
+        let mut next = Some(init);
+        while let Some(c) = next {
+            // Identifiers can contain `\uXXXX` escapes
+            if c == '\\' {
+                if self.read() != Some('u') {
+                    return Err(Error::InvalidEscape(start));
+                }
+                self.read_hex(4, start, start)?;
+            }
 
-        match self.read() {
+            next = match self.peek_char() {
+                Some(c) if is_identifier_char(c) || c == '\\' => self.read(),
+                _ => None,
+            };
+        }
+
+        let token = match self.slice(start) {
+            "null" => Token::Null,
+            "true" => Token::True,
+            "false" => Token::False,
+            word @ "Infinity" | word @ "NaN" => Token::Number(word.into()),
+            word => Token::Identifier(word.into()),
+        };
+
+        Ok(token)
+    }
+
+    pub fn next_token(&mut self) -> Result<Token<'a>, Error> {
+        let token = self.read_token();
+        if exceeds(self.settings.limits.max_input_bytes, self.position.offset) {
+            return Err(Error::LimitExceeded(Limit::InputBytes, self.start));
+        }
+
+        let token = token?;
+        let length = self.position.offset - self.start.offset;
+        let exceeded = match token {
+            // Without the quotes
+            Token::String(_) | Token::SingleQuoted(_) => {
+                exceeds(self.settings.limits.max_string_bytes, length - 2)
+                    .then_some(Limit::StringBytes)
+            }
+            Token::Identifier(_) => {
+                exceeds(self.settings.limits.max_string_bytes, length).then_some(Limit::StringBytes)
+            }
+            Token::Number(_) => exceeds(self.settings.limits.max_number_length, length)
+                .then_some(Limit::NumberLength),
+            _ => None,
+        };
+        match exceeded {
+            Some(limit) => Err(Error::LimitExceeded(limit, self.start)),
+            None => Ok(token),
+        }
+    }
+
+    fn read_token(&mut self) -> Result<Token<'a>, Error> {
+        self.exhausted = false;
+        // A byte order mark may start the input, RFC 8259 section 8.1 allows
+        // ignoring it. It takes no column, as in editors.
+        if self.position.offset == 0 && self.rest().starts_with('\u{feff}') {
+            self.position.offset += '\u{feff}'.len_utf8();
+        }
+        self.skip_whitespace()?;
+
+        let start = self.position;
+        self.start = start;
+        let token = match self.read() {
+            Some(c) if self.settings.json5 => self.read_json5_token(c, start)?,
             Some(':') => Token::Colon,
             Some(',') => Token::Comma,
             Some('{') => Token::ObjectStart,
             Some('}') => Token::ObjectEnd,
             Some('[') => Token::ArrayStart,
             Some(']') => Token::ArrayEnd,
-            Some(c) => {
-                if c == 'n' && self.read_ident("ull") {
-                    Token::Null
-                } else if c == 't' && self.read_ident("rue") {
-                    Token::True
-                } else if c == 'f' && self.read_ident("alse") {
-                    Token::False
-                } else if c == '"' {
-                    let c = self.read().unwrap();
-                    self.read_string(c)
-                } else if c.is_numeric() || c == '-' {
-                    self.read_number(c)
-                } else {
-                    Token::Error
-                }
-            }
+            Some('n') => self.read_literal("ull", Token::Null)?,
+            Some('t') => self.read_literal("rue", Token::True)?,
+            Some('f') => self.read_literal("alse", Token::False)?,
+            Some('"') => Token::String(self.read_string('"', start)?),
+            Some(c) if c.is_ascii_digit() || c == '-' => self.read_number(start)?,
+            Some(c) => return Err(Error::UnexpectedCharacter(c, start)),
             None => Token::EndOfFile,
-        }
+        };
+
+        Ok(token)
+    }
+
+    fn read_json5_token(&mut self, c: char, start: Position) -> Result<Token<'a>, Error> {
+        let token = match c {
+            ':' => Token::Colon,
+            ',' => Token::Comma,
+            '{' => Token::ObjectStart,
+            '}' => Token::ObjectEnd,
+            '[' => Token::ArrayStart,
+            ']' => Token::ArrayEnd,
+            '"' => Token::String(self.read_string('"', start)?),
+            '\'' => Token::SingleQuoted(self.read_string('\'', start)?),
+            c if c.is_ascii_digit() || "+-.".contains(c) => self.read_json5_number(start)?,
+            c if is_identifier_char(c) || c == '\\' => self.read_word(c, start)?,
+            c => return Err(Error::UnexpectedCharacter(c, start)),
+        };
+
+        Ok(token)
     }
+}
 
-    pub fn read_to_end(&mut self) -> Vec<Token> {
-        let mut tokens = Vec::new();
-        while self.peek() != None {
-            tokens.push(self.next_token());
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Result<(Token<'a>, Span), Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
         }
 
-        tokens
+        match self.next_token() {
+            Ok(Token::EndOfFile) => {
+                self.done = true;
+                None
+            }
+            Ok(token) => Some(Ok((
+                token,
+                Span {
+                    start: self.start,
+                    end: self.position,
+                },
+            ))),
+            Err(error) => {
+                self.done = true;
+                Some(Err(error))
+            }
+        }
     }
 }
 
+impl std::iter::FusedIterator for Lexer<'_> {}
+
+/// Whether `value` is over `limit`, if there is one.
+pub(crate) fn exceeds(limit: Option<usize>, value: usize) -> bool {
+    limit.is_some_and(|limit| value > limit)
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '$' || c == '_'
+}
+
+fn is_json5_number(number: &str) -> bool {
+    let unsigned = number.trim_start_matches(['+', '-']);
+    if number.len() - unsigned.len() > 1 {
+        return false;
+    }
+    if unsigned == "Infinity" || unsigned == "NaN" {
+        return true;
+    }
+    if let Some(hex) = unsigned
+        .strip_prefix("0x")
+        .or_else(|| unsigned.strip_prefix("0X"))
+    {
+        return !hex.is_empty() && hex.chars().all(|c| c.is_ascii_hexdigit());
+    }
+
+    let (mantissa, exponent) = match unsigned.find(['e', 'E']) {
+        Some(e) => (&unsigned[..e], Some(&unsigned[e + 1..])),
+        None => (unsigned, None),
+    };
+    let (integer, fraction) = match mantissa.find('.') {
+        Some(dot) => (&mantissa[..dot], &mantissa[dot + 1..]),
+        None => (mantissa, ""),
+    };
+    let digits = |s: &str| s.chars().all(|c| c.is_ascii_digit());
+    let exponent_valid = exponent.is_none_or(|exponent| {
+        let exponent = exponent.strip_prefix(['+', '-']).unwrap_or(exponent);
+        !exponent.is_empty() && digits(exponent)
+    });
+
+    (!integer.is_empty() || !fraction.is_empty())
+        && digits(integer)
+        && digits(fraction)
+        && !(integer.len() > 1 && integer.starts_with('0'))
+        && exponent_valid
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -146,24 +713,43 @@ mod test {
     fn assert_lex(source: &str, tokens: &[Token]) {
         let mut lexer = Lexer::new(source);
         for tok in tokens {
-            let token = lexer.next_token();
+            let token = lexer.next_token().unwrap();
             assert_eq!(&token, tok);
         }
     }
 
+    fn assert_error(source: &str, error: Error) {
+        let mut lexer = Lexer::new(source);
+        loop {
+            match lexer.next_token() {
+                Ok(Token::EndOfFile) => panic!("expected {:?}", error),
+                Ok(_) => continue,
+                Err(e) => return assert_eq!(e, error),
+            }
+        }
+    }
+
+    fn at(line: usize, column: usize, offset: usize) -> Position {
+        Position {
+            line,
+            column,
+            offset,
+        }
+    }
+
     #[test]
     fn parse_simple() {
         assert_lex(
             r#"{"a": "b", "c": "d"}"#,
-            &vec![
+            &[
                 Token::ObjectStart,
-                Token::String("a".to_string()),
+                Token::String("a".into()),
                 Token::Colon,
-                Token::String("b".to_string()),
+                Token::String("b".into()),
                 Token::Comma,
-                Token::String("c".to_string()),
+                Token::String("c".into()),
                 Token::Colon,
-                Token::String("d".to_string()),
+                Token::String("d".into()),
                 Token::ObjectEnd,
                 Token::EndOfFile,
             ],
@@ -174,22 +760,22 @@ mod test {
     fn parse_nested() {
         assert_lex(
             r#"{"a": [1, 2], "b": {"c": 3}}"#,
-            &vec![
+            &[
                 Token::ObjectStart,
-                Token::String("a".to_string()),
+                Token::String("a".into()),
                 Token::Colon,
                 Token::ArrayStart,
-                Token::Number("1".to_string()),
+                Token::Number("1".into()),
                 Token::Comma,
-                Token::Number("2".to_string()),
+                Token::Number("2".into()),
                 Token::ArrayEnd,
                 Token::Comma,
-                Token::String("b".to_string()),
+                Token::String("b".into()),
                 Token::Colon,
                 Token::ObjectStart,
-                Token::String("c".to_string()),
+                Token::String("c".into()),
                 Token::Colon,
-                Token::Number("3".to_string()),
+                Token::Number("3".into()),
                 Token::ObjectEnd,
                 Token::ObjectEnd,
                 Token::EndOfFile,
@@ -197,26 +783,316 @@ mod test {
         );
     }
 
+    #[test]
+    fn strings() {
+        assert_lex(r#""""#, &[Token::String("".into())]);
+        assert_lex(
+            r#"["a\\", "b"]"#,
+            &[
+                Token::ArrayStart,
+                Token::String(r#"a\\"#.into()),
+                Token::Comma,
+                Token::String("b".into()),
+                Token::ArrayEnd,
+            ],
+        );
+        assert_lex(
+            r#""\/\b\f\n\r\t""#,
+            &[Token::String(r#"\/\b\f\n\r\t"#.into())],
+        );
+        assert_lex(
+            r#""\u00e9\uD83D\uDE00""#,
+            &[Token::String(r#"\u00e9\uD83D\uDE00"#.into())],
+        );
+        assert_error(r#""\x""#, Error::InvalidEscape(at(1, 2, 1)));
+        assert_error(r#""\u12""#, Error::InvalidEscape(at(1, 2, 1)));
+        assert_error(r#""\uD83D""#, Error::UnpairedSurrogate(at(1, 2, 1)));
+        assert_error(r#""\uD83D\u0041""#, Error::UnpairedSurrogate(at(1, 2, 1)));
+        assert_error(r#""\uDE00""#, Error::UnpairedSurrogate(at(1, 2, 1)));
+        assert_error("\"a\nb\"", Error::ControlCharacter(at(1, 3, 2)));
+        assert_error("\"a\u{1f}\"", Error::ControlCharacter(at(1, 3, 2)));
+        assert_error(r#"["abc"#, Error::UnterminatedString(at(1, 2, 1)));
+        assert_error(r#""\u00"#, Error::UnterminatedString(at(1, 1, 0)));
+    }
+
+    #[test]
+    fn errors() {
+        assert_error("[1,\n  ?]", Error::UnexpectedCharacter('?', at(2, 3, 6)));
+        assert_error("[nul", Error::UnexpectedEof(at(1, 5, 4)));
+        assert_error("trve", Error::UnexpectedCharacter('v', at(1, 3, 2)));
+        assert_error("1.2.3abc", Error::InvalidNumber(at(1, 1, 0)));
+        assert_error("[-]", Error::InvalidNumber(at(1, 2, 1)));
+    }
+
+    #[test]
+    fn numbers() {
+        let number = |n: &'static str| Token::Number(n.into());
+        assert_lex("0", &[number("0")]);
+        assert_lex("-0.0e+0", &[number("-0.0e+0")]);
+        assert_lex(
+            "123456789012345678901234567890",
+            &[number("123456789012345678901234567890")],
+        );
+        assert_lex(
+            "{1:2}",
+            &[Token::ObjectStart, number("1"), Token::Colon, number("2")],
+        );
+        assert_lex("[1/**/]", &[Token::ArrayStart, number("1")]);
+        for source in &[
+            "01", "-01", "1.", "1.e3", "1e", "1e+", "--1", "-a", "1.2.3", "12abc", "1e3.5", "0x1",
+            "1+2",
+        ] {
+            assert_error(source, Error::InvalidNumber(at(1, 1, 0)));
+        }
+        assert_error(".5", Error::UnexpectedCharacter('.', at(1, 1, 0)));
+        assert_error("+1", Error::UnexpectedCharacter('+', at(1, 1, 0)));
+        // Only space, tab, line feed and carriage return are whitespace
+        assert_error(
+            "[\u{a0}1]",
+            Error::UnexpectedCharacter('\u{a0}', at(1, 2, 1)),
+        );
+        assert_error(
+            "[1,\u{2003}2]",
+            Error::UnexpectedCharacter('\u{2003}', at(1, 4, 3)),
+        );
+        assert_error(
+            "\u{b}null",
+            Error::UnexpectedCharacter('\u{b}', at(1, 1, 0)),
+        );
+    }
+
+    #[test]
+    fn positions() {
+        let mut lexer = Lexer::new("[\"wørld 🎉\",\r\n\t\"a\"]");
+        let starts = [
+            at(1, 1, 0),
+            at(1, 2, 1),
+            at(1, 11, 14),
+            at(2, 2, 18),
+            at(2, 5, 21),
+        ];
+        for &start in &starts {
+            lexer.next_token().unwrap();
+            assert_eq!(start, lexer.start());
+        }
+    }
+
+    #[test]
+    fn byte_order_mark() {
+        assert_lex(
+            "\u{feff}[1]",
+            &[
+                Token::ArrayStart,
+                Token::Number("1".into()),
+                Token::ArrayEnd,
+                Token::EndOfFile,
+            ],
+        );
+        let mut lexer = Lexer::new("\u{feff}\n1");
+        lexer.next_token().unwrap();
+        assert_eq!(lexer.start(), at(2, 1, 4));
+        // Only at the start of the input
+        assert_error(
+            "[\u{feff}1]",
+            Error::UnexpectedCharacter('\u{feff}', at(1, 2, 1)),
+        );
+    }
+
+    #[test]
+    fn iterator() {
+        let spans: Vec<_> = Lexer::new("[\"wørld\",\n 12]")
+            .map(|item| {
+                let (_, span) = item.unwrap();
+                (span.start.offset, span.end.offset, span.end.line)
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            [(0, 1, 1), (1, 9, 1), (9, 10, 1), (12, 14, 2), (14, 15, 2)]
+        );
+
+        // Stops after the first error
+        let mut lexer = Lexer::new("[1, ?, 2]");
+        let items: Vec<_> = lexer.by_ref().collect();
+        assert_eq!(items.len(), 4);
+        assert_eq!(items[3], Err(Error::UnexpectedCharacter('?', at(1, 5, 4))));
+        assert!(lexer.next().is_none());
+    }
+
+    #[test]
+    fn borrowed() {
+        let mut lexer = Lexer::new(r#"["a\u0041", 1.5e3, true]"#);
+        lexer.next_token().unwrap();
+        for _ in 0..2 {
+            match lexer.next_token().unwrap() {
+                Token::String(text) | Token::Number(text) => {
+                    assert!(matches!(text, Cow::Borrowed(_)))
+                }
+                token => panic!("unexpected {:?}", token),
+            }
+            lexer.next_token().unwrap();
+        }
+    }
+
+    #[test]
+    fn decode() {
+        let decode = |source: &'static str, json5: bool| {
+            let mut lexer = Lexer::new(source).json5(json5).decode_escapes(true);
+            match lexer.next_token().unwrap() {
+                Token::String(string) | Token::SingleQuoted(string) => string,
+                token => panic!("unexpected {:?}", token),
+            }
+        };
+        assert!(matches!(
+            decode(r#""plain é""#, false),
+            Cow::Borrowed("plain é")
+        ));
+        assert_eq!(
+            decode(
+                r#""caf\u00e9 \/ \ud83d\ude00 \n \" \\ \u0022 \u001F \u0041""#,
+                false
+            ),
+            r#"café / 😀 \n \" \\ \" \u001f A"#
+        );
+        assert_eq!(
+            decode("'\\x41\\'\\v\\0 \"\\\n!'", true),
+            r#"A\'\u000b\u0000 "!"#
+        );
+        assert_eq!(decode("'\t\\\r\n!'", true), "\t!");
+        assert!(matches!(
+            Lexer::new(r#""é\ud800""#).decode_escapes(true).next_token(),
+            Err(Error::UnpairedSurrogate(_))
+        ));
+    }
+
+    #[test]
+    fn comments() {
+        let assert_comments = |source: &str, tokens: &[Token]| {
+            let mut lexer = Lexer::new(source).comments(true);
+            for tok in tokens {
+                assert_eq!(&lexer.next_token().unwrap(), tok);
+            }
+        };
+        assert_comments("// only a comment", &[Token::EndOfFile]);
+        assert_comments(
+            "/* a\n * b **/ [1/**/, // c\n2]",
+            &[
+                Token::ArrayStart,
+                Token::Number("1".into()),
+                Token::Comma,
+                Token::Number("2".into()),
+                Token::ArrayEnd,
+                Token::EndOfFile,
+            ],
+        );
+        assert_comments(
+            r#""/* not // a comment""#,
+            &[Token::String("/* not // a comment".into())],
+        );
+
+        let error = |source: &str| Lexer::new(source).comments(true).next_token().unwrap_err();
+        assert_eq!(error("  /* a"), Error::UnterminatedComment(at(1, 3, 2)));
+        assert_eq!(error("/ a"), Error::UnexpectedCharacter('/', at(1, 1, 0)));
+        assert_error("// a", Error::UnexpectedCharacter('/', at(1, 1, 0)));
+    }
+
+    #[test]
+    fn json5() {
+        let assert_json5 = |source: &str, tokens: &[Token]| {
+            let mut lexer = Lexer::new(source).json5(true);
+            for tok in tokens {
+                assert_eq!(&lexer.next_token().unwrap(), tok);
+            }
+        };
+        let string = |s: &'static str| Token::String(s.into());
+        let number = |s: &'static str| Token::Number(s.into());
+        assert_json5(
+            "{$a_1: 'b\"', null: Infinity} // end",
+            &[
+                Token::ObjectStart,
+                Token::Identifier("$a_1".into()),
+                Token::Colon,
+                Token::SingleQuoted("b\"".into()),
+                Token::Comma,
+                Token::Null,
+                Token::Colon,
+                number("Infinity"),
+                Token::ObjectEnd,
+                Token::EndOfFile,
+            ],
+        );
+        assert_json5(
+            "[0x1F, .5, 5., +1, -NaN, 1e+3]",
+            &[
+                Token::ArrayStart,
+                number("0x1F"),
+                Token::Comma,
+                number(".5"),
+                Token::Comma,
+                number("5."),
+                Token::Comma,
+                number("+1"),
+                Token::Comma,
+                number("-NaN"),
+                Token::Comma,
+                number("1e+3"),
+                Token::ArrayEnd,
+            ],
+        );
+        assert_json5(
+            "\u{feff}\u{a0}[\u{2028}1\u{b}]",
+            &[Token::ArrayStart, number("1"), Token::ArrayEnd],
+        );
+        assert_json5(r#""a\x41\v\0\'\q""#, &[string(r#"a\x41\v\0\'\q"#)]);
+        assert_json5(
+            "'a\\\r\nb\tc'",
+            &[Token::SingleQuoted("a\\\r\nb\tc".into())],
+        );
+        assert_json5("\\u0061b", &[Token::Identifier("\\u0061b".into())]);
+
+        let error = |source: &str| {
+            let mut lexer = Lexer::new(source).json5(true);
+            loop {
+                match lexer.next_token() {
+                    Ok(Token::EndOfFile) => panic!("{} lexed without errors", source),
+                    Ok(_) => continue,
+                    Err(e) => return e,
+                }
+            }
+        };
+        assert_eq!(error("'a\nb'"), Error::ControlCharacter(at(1, 3, 2)));
+        assert_eq!(error(r#"'\01'"#), Error::InvalidEscape(at(1, 2, 1)));
+        assert_eq!(error(r#"'\1'"#), Error::InvalidEscape(at(1, 2, 1)));
+        assert_eq!(error(r#"'\xZZ'"#), Error::InvalidEscape(at(1, 2, 1)));
+        for number in &["0x", "0xG", "1.2.3", "01", "++1", "1e", ".", "-Infinit"] {
+            assert_eq!(
+                error(&format!("[{}]", number)),
+                Error::InvalidNumber(at(1, 2, 1)),
+                "{}",
+                number
+            );
+        }
+    }
+
     #[test]
     fn grammar() {
-        assert_lex("  \t\n\r", &vec![]);
-        assert_lex("null", &vec![Token::Null]);
-        assert_lex("[]", &vec![Token::ArrayStart, Token::ArrayEnd]);
-        assert_lex("{}", &vec![Token::ObjectStart, Token::ObjectEnd]);
-        assert_lex("15.2", &vec![Token::Number("15.2".to_string())]);
-        assert_lex("0.2", &vec![Token::Number("0.2".to_string())]);
-        assert_lex("5e9", &vec![Token::Number("5e9".to_string())]);
-        assert_lex("-4E-3", &vec![Token::Number("-4E-3".to_string())]);
-        assert_lex("true", &vec![Token::True]);
-        assert_lex("false", &vec![Token::False]);
-        assert_lex(r#"" ""#, &vec![Token::String(" ".to_string())]);
-        assert_lex(r#""a""#, &vec![Token::String("a".to_string())]);
-        // TODO: Make these two work...
-        // assert_lex(r#""\"""#, &vec![Token::String("\"".to_string())]);
-        // assert_lex(r#""\\""#, &vec![Token::String("\\".to_string())]);
+        assert_lex("  \t\n\r", &[]);
+        assert_lex("null", &[Token::Null]);
+        assert_lex("[]", &[Token::ArrayStart, Token::ArrayEnd]);
+        assert_lex("{}", &[Token::ObjectStart, Token::ObjectEnd]);
+        assert_lex("15.2", &[Token::Number("15.2".into())]);
+        assert_lex("0.2", &[Token::Number("0.2".into())]);
+        assert_lex("5e9", &[Token::Number("5e9".into())]);
+        assert_lex("-4E-3", &[Token::Number("-4E-3".into())]);
+        assert_lex("true", &[Token::True]);
+        assert_lex("false", &[Token::False]);
+        assert_lex(r#"" ""#, &[Token::String(" ".into())]);
+        assert_lex(r#""a""#, &[Token::String("a".into())]);
+        assert_lex(r#""\"""#, &[Token::String(r#"\""#.into())]);
+        assert_lex(r#""\\""#, &[Token::String(r#"\\"#.into())]);
         assert_lex(
             "[null,]",
-            &vec![
+            &[
                 Token::ArrayStart,
                 Token::Null,
                 Token::Comma,
@@ -0,0 +1,277 @@
+use crate::error::{Error, Position};
+use crate::json5;
+use crate::tokens::Token;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Container {
+    Object,
+    Array,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Expect {
+    /// Any value.
+    Value,
+    /// A value or the end of the array that was just opened.
+    FirstValue,
+    /// An object key.
+    Key,
+    /// An object key or the end of the object that was just opened.
+    FirstKey,
+    /// The colon between a key and its value.
+    Colon,
+    /// A comma or the end of the current array or object.
+    Separator,
+    /// Nothing, the top level value is complete.
+    End,
+}
+
+/// A state machine that checks that a stream of tokens follows the JSON
+/// grammar, one token at a time.
+#[derive(Debug, Clone)]
+pub(crate) struct Validator {
+    stack: Vec<Container>,
+    expect: Expect,
+    json5: bool,
+}
+
+impl Validator {
+    pub fn new() -> Self {
+        Validator {
+            stack: Vec::new(),
+            expect: Expect::Value,
+            json5: false,
+        }
+    }
+
+    /// Whether to allow the JSON5 additions to the grammar: trailing commas
+    /// and unquoted keys.
+    pub fn json5(mut self, json5: bool) -> Self {
+        self.json5 = json5;
+        self
+    }
+
+    /// Starts over at the beginning of a document.
+    pub fn reset(&mut self) {
+        self.stack.clear();
+        self.expect = Expect::Value;
+    }
+
+    /// Whether the next token has to be an object key.
+    pub fn expects_key(&self) -> bool {
+        matches!(self.expect, Expect::Key | Expect::FirstKey)
+    }
+
+    /// Whether the next token has to be a value.
+    pub fn expects_value(&self) -> bool {
+        matches!(self.expect, Expect::Value | Expect::FirstValue)
+    }
+
+    /// Goes back to before the comma that was just checked, to drop it.
+    pub fn undo_comma(&mut self) {
+        self.expect = Expect::Separator;
+    }
+
+    /// Checks the next token, which starts at `position`.
+    pub fn check(&mut self, token: &Token, position: Position) -> Result<(), Error> {
+        match (self.expect, token) {
+            (_, Token::EndOfFile) if self.expect != Expect::End => {
+                return Err(Error::UnexpectedEof(position));
+            }
+            (Expect::End, Token::EndOfFile) => {}
+            (Expect::Value | Expect::FirstValue, Token::ArrayStart) => {
+                self.stack.push(Container::Array);
+                self.expect = Expect::FirstValue;
+            }
+            (Expect::Value | Expect::FirstValue, Token::ObjectStart) => {
+                self.stack.push(Container::Object);
+                self.expect = Expect::FirstKey;
+            }
+            (Expect::Value | Expect::FirstValue, Token::String(_))
+            | (Expect::Value | Expect::FirstValue, Token::SingleQuoted(_))
+            | (Expect::Value | Expect::FirstValue, Token::Number(_))
+            | (Expect::Value | Expect::FirstValue, Token::Null)
+            | (Expect::Value | Expect::FirstValue, Token::True)
+            | (Expect::Value | Expect::FirstValue, Token::False) => self.end_value(),
+            (Expect::Key | Expect::FirstKey, Token::String(_)) => self.expect = Expect::Colon,
+            (Expect::Key | Expect::FirstKey, key) if self.json5 && json5::is_key(key) => {
+                self.expect = Expect::Colon
+            }
+            (Expect::Colon, Token::Colon) => self.expect = Expect::Value,
+            (Expect::Separator, Token::Comma) => {
+                self.expect = match self.stack.last() {
+                    Some(Container::Object) => Expect::Key,
+                    _ => Expect::Value,
+                };
+            }
+            (Expect::FirstValue, Token::ArrayEnd) | (Expect::FirstKey, Token::ObjectEnd) => {
+                self.close()
+            }
+            // A trailing comma
+            (Expect::Key, Token::ObjectEnd) if self.json5 => self.close(),
+            (Expect::Value, Token::ArrayEnd)
+                if self.json5 && self.stack.last() == Some(&Container::Array) =>
+            {
+                self.close()
+            }
+            (Expect::Separator, Token::ArrayEnd)
+                if self.stack.last() == Some(&Container::Array) =>
+            {
+                self.close()
+            }
+            (Expect::Separator, Token::ObjectEnd)
+                if self.stack.last() == Some(&Container::Object) =>
+            {
+                self.close()
+            }
+            _ => return Err(Error::UnexpectedToken(token.describe(), position)),
+        }
+
+        Ok(())
+    }
+
+    /// Moves past a token the grammar does not allow here as well as it
+    /// can, so the state stays useful in lenient mode.
+    pub fn advance(&mut self, token: &Token) {
+        match token {
+            Token::ArrayStart => {
+                self.stack.push(Container::Array);
+                self.expect = Expect::FirstValue;
+            }
+            Token::ObjectStart => {
+                self.stack.push(Container::Object);
+                self.expect = Expect::FirstKey;
+            }
+            Token::ArrayEnd | Token::ObjectEnd => self.close(),
+            Token::Comma => {
+                self.expect = match self.stack.last() {
+                    Some(Container::Object) => Expect::Key,
+                    _ => Expect::Value,
+                };
+            }
+            Token::Colon => self.expect = Expect::Value,
+            Token::EndOfFile => {}
+            _ if self.expects_key() => self.expect = Expect::Colon,
+            _ => self.end_value(),
+        }
+    }
+
+    fn close(&mut self) {
+        self.stack.pop();
+        self.end_value();
+    }
+
+    fn end_value(&mut self) {
+        self.expect = if self.stack.is_empty() {
+            Expect::End
+        } else {
+            Expect::Separator
+        };
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::tokens::Lexer;
+
+    fn validate(source: &str) -> Result<(), Error> {
+        let mut lexer = Lexer::new(source);
+        let mut validator = Validator::new();
+        loop {
+            let token = lexer.next_token()?;
+            validator.check(&token, lexer.start())?;
+            if token == Token::EndOfFile {
+                return Ok(());
+            }
+        }
+    }
+
+    fn assert_unexpected(source: &str, token: &'static str, column: usize) {
+        match validate(source) {
+            Err(Error::UnexpectedToken(t, position)) => {
+                assert_eq!((t, position.column), (token, column), "{}", source)
+            }
+            result => panic!(
+                "{}: expected unexpected {}, got {:?}",
+                source, token, result
+            ),
+        }
+    }
+
+    #[test]
+    fn valid() {
+        for source in &[
+            "null",
+            " 15.2 ",
+            r#""a""#,
+            "[]",
+            "{}",
+            "[1, [true, false], {}]",
+            r#"{"a": {"b": [null]}, "c": "d"}"#,
+        ] {
+            assert_eq!(validate(source), Ok(()), "{}", source);
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        assert_unexpected("[null,]", "']'", 7);
+        assert_unexpected(r#"{"a" "b"}"#, "string", 6);
+        assert_unexpected("}}{{", "'}'", 1);
+        assert_unexpected("[1 2]", "number", 4);
+        assert_unexpected("{1: 2}", "number", 2);
+        assert_unexpected(r#"{"a": 1,}"#, "'}'", 9);
+        assert_unexpected("[1}", "'}'", 3);
+        assert_unexpected("null null", "null", 6);
+        assert_unexpected("[,]", "','", 2);
+    }
+
+    #[test]
+    fn json5() {
+        let validate = |source: &str| {
+            let mut lexer = Lexer::new(source).json5(true);
+            let mut validator = Validator::new().json5(true);
+            loop {
+                let token = lexer.next_token()?;
+                validator.check(&token, lexer.start())?;
+                if token == Token::EndOfFile {
+                    return Ok(());
+                }
+            }
+        };
+        assert_eq!(validate("[1, 2,]"), Ok(()));
+        assert_eq!(validate("{a: 'b', null: 1, Infinity: NaN,}"), Ok(()));
+        assert!(matches!(
+            validate("[1,,]"),
+            Err(Error::UnexpectedToken("','", _))
+        ));
+        assert!(matches!(
+            validate("{,}"),
+            Err(Error::UnexpectedToken("','", _))
+        ));
+        assert!(matches!(
+            validate("{a: b}"),
+            Err(Error::UnexpectedToken("identifier", _))
+        ));
+        assert!(matches!(
+            validate("{a:}"),
+            Err(Error::UnexpectedToken("'}'", _))
+        ));
+        assert!(matches!(
+            validate("{+Infinity: 1}"),
+            Err(Error::UnexpectedToken("number", _))
+        ));
+    }
+
+    #[test]
+    fn truncated() {
+        for source in &["", "  ", "[", r#"{"a""#, r#"{"a":"#, "[1,"] {
+            assert!(
+                matches!(validate(source), Err(Error::UnexpectedEof(_))),
+                "{}",
+                source
+            );
+        }
+    }
+}
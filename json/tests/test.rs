@@ -15,7 +15,7 @@ fn read_file(filename: &str) -> String {
 fn large() {
     let large = read_file("./tests/fixtures/large.json");
     let large_correct = read_file("./tests/fixtures/large_correct.json");
-    let minified = Minify::new(large.as_str());
+    let minified = Minify::new(large.as_str()).unwrap();
 
     assert_eq!(large_correct.as_str(), format!("{}", minified));
 }
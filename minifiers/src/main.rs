@@ -1,30 +1,403 @@
+use diagnostics::{Renderer, Report};
 use json::minify::Minify;
-use std::fs::File;
-use std::io::BufReader;
-use std::io::Read;
-use std::path::PathBuf;
+use json::ndjson;
+use json::prettify::{Indent, Prettify, Style};
+use json::stream::Minifier;
+use json::{DuplicateKeys, Encoding, Escapes, Limits, Options, Precision};
+use std::cell::RefCell;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::rc::Rc;
 use structopt::StructOpt;
 
 #[derive(Debug, StructOpt)]
 #[structopt(name = "minifiers")]
 struct Opt {
-    /// Files to process
+    /// Minify JSON without checking that it is well-formed
+    #[structopt(long = "lenient")]
+    lenient: bool,
+
+    /// Strip `//` and `/* */` comments, always enabled for .jsonc files
+    #[structopt(long = "comments")]
+    comments: bool,
+
+    /// Write .json5 files as compact JSON5 instead of converting them to JSON
+    #[structopt(long = "keep-json5")]
+    keep_json5: bool,
+
+    /// Write canonical JSON (RFC 8785) with sorted keys and normalized numbers
+    /// and strings
+    #[structopt(long = "canonical")]
+    canonical: bool,
+
+    /// Write numbers in their shortest form, like `1.50` as `1.5`
+    #[structopt(long = "normalize-numbers")]
+    normalize_numbers: bool,
+
+    /// Write `-0` as `0` when normalizing numbers
+    #[structopt(long = "normalize-negative-zero")]
+    normalize_negative_zero: bool,
+
+    /// Round numbers to this many significant digits
+    #[structopt(long = "precision")]
+    precision: Option<usize>,
+
+    /// Round numbers to this many decimal places
+    #[structopt(long = "decimals")]
+    decimals: Option<usize>,
+
+    /// Only round numbers at or below this JSON Pointer, where `*` matches any
+    /// key or index
+    #[structopt(long = "precision-path")]
+    precision_paths: Vec<String>,
+
+    /// Decode escapes in strings that are not needed, like `\u00e9`
+    #[structopt(long = "decode-escapes")]
+    decode_escapes: bool,
+
+    /// Escape every non-ASCII character in strings as `\uXXXX`
+    #[structopt(long = "ascii")]
+    ascii: bool,
+
+    /// Escape `<`, `>`, `&`, U+2028 and U+2029 so the output can be embedded
+    /// in HTML
+    #[structopt(long = "html-safe")]
+    html_safe: bool,
+
+    /// Fail on keys that appear more than once in an object (`error`), or keep
+    /// only the `first` or `last` of them
+    #[structopt(long = "duplicate-keys", parse(try_from_str = "parse_duplicate_keys"))]
+    duplicate_keys: Option<DuplicateKeys>,
+
+    /// Drop object members whose value is `null`
+    #[structopt(long = "drop-nulls")]
+    drop_nulls: bool,
+
+    /// Drop object members whose value is an empty array or object
+    #[structopt(long = "drop-empty")]
+    drop_empty: bool,
+
+    /// Drop object members with this key
+    #[structopt(long = "drop-key")]
+    drop_keys: Vec<String>,
+
+    /// Drop object members at this JSON Pointer, where `*` matches any key or
+    /// index
+    #[structopt(long = "drop-path")]
+    drop_paths: Vec<String>,
+
+    /// Minify every line as a separate document, always enabled for .ndjson
+    /// and .jsonl files
+    #[structopt(long = "ndjson")]
+    ndjson: bool,
+
+    /// Fail on arrays and objects nested deeper than this
+    #[structopt(long = "max-depth")]
+    max_depth: Option<usize>,
+
+    /// Fail on strings longer than this many bytes
+    #[structopt(long = "max-string-bytes")]
+    max_string_bytes: Option<usize>,
+
+    /// Fail on numbers longer than this
+    #[structopt(long = "max-number-length")]
+    max_number_length: Option<usize>,
+
+    /// Fail on input with more tokens than this
+    #[structopt(long = "max-tokens")]
+    max_tokens: Option<usize>,
+
+    /// Fail on input larger than this many bytes
+    #[structopt(long = "max-input-bytes")]
+    max_input_bytes: Option<usize>,
+
+    /// Minify invalid JSON as well as possible instead of failing, and warn
+    /// about every fix
+    #[structopt(long = "recover")]
+    recover: bool,
+
+    /// Pretty-print instead of minifying
+    #[structopt(long = "pretty")]
+    pretty: bool,
+
+    /// Spaces per indentation level when pretty-printing
+    #[structopt(long = "indent", default_value = "2")]
+    indent: usize,
+
+    /// Indent with tabs when pretty-printing
+    #[structopt(long = "tabs")]
+    tabs: bool,
+
+    /// Keep arrays and objects that fit within this width on one line when
+    /// pretty-printing
+    #[structopt(long = "width")]
+    width: Option<usize>,
+
+    /// Files to process. .css files are only checked for errors, when built
+    /// with the `css` feature
     #[structopt(name = "FILE", parse(from_os_str))]
     files: Vec<PathBuf>,
 }
 
-fn main() -> Result<(), Box<std::error::Error>> {
+fn parse_duplicate_keys(policy: &str) -> Result<DuplicateKeys, String> {
+    match policy {
+        "error" => Ok(DuplicateKeys::Error),
+        "first" => Ok(DuplicateKeys::KeepFirst),
+        "last" => Ok(DuplicateKeys::KeepLast),
+        _ => Err(format!("expected error, first or last, not {:?}", policy)),
+    }
+}
+
+fn main() {
     let opt = Opt::from_args();
+    let renderer = Renderer::for_stderr();
+    let mut failed = false;
+    let mut invalid_records = 0;
     for file in &opt.files {
-        if file.extension().unwrap() == "json" {
-            let file = File::open(file)?;
-            let mut buf_reader = BufReader::new(file);
-            let mut contents = String::new();
-            buf_reader.read_to_string(&mut contents)?;
-            let minified = Minify::new(contents.as_str());
-            println!("{}", minified);
+        let mut reporter = Reporter::new(file, renderer);
+        match process_file(&opt, file, &mut reporter) {
+            Ok(invalid) => invalid_records += invalid,
+            Err(error) => {
+                reporter.error(&*error);
+                failed = true;
+            }
         }
     }
 
+    if invalid_records > 0 {
+        let message = format!("left out invalid records: {}", invalid_records);
+        eprint!("{}", renderer.render("", "", &Report::error(message)));
+        failed = true;
+    }
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Writes the processed file to stdout, and returns how many NDJSON records
+/// were left out.
+fn process_file(opt: &Opt, file: &Path, reporter: &mut Reporter) -> Result<usize, Box<dyn Error>> {
+    let stdout = io::stdout();
+    let extension = match file.extension() {
+        Some(extension) => extension,
+        None => return Ok(0),
+    };
+    #[cfg(feature = "css")]
+    {
+        if extension == "css" {
+            return check_css(file, reporter);
+        }
+    }
+    let ndjson = opt.ndjson || extension == "ndjson" || extension == "jsonl";
+    if !ndjson && extension != "json" && extension != "jsonc" && extension != "json5" {
+        return Ok(0);
+    }
+
+    let options = Options {
+        lenient: opt.lenient,
+        comments: opt.comments || extension == "jsonc",
+        json5: extension == "json5",
+        keep_json5: opt.keep_json5,
+        canonical: opt.canonical,
+        normalize_numbers: opt.normalize_numbers,
+        normalize_negative_zero: opt.normalize_negative_zero,
+        precision: match (opt.decimals, opt.precision) {
+            (Some(places), _) => Some(Precision::DecimalPlaces(places)),
+            (None, Some(digits)) => Some(Precision::SignificantDigits(digits)),
+            (None, None) => None,
+        },
+        precision_paths: opt.precision_paths.clone(),
+        escapes: if opt.ascii {
+            Escapes::Ascii
+        } else if opt.decode_escapes {
+            Escapes::Decode
+        } else {
+            Escapes::Keep
+        },
+        html_safe: opt.html_safe,
+        duplicate_keys: opt.duplicate_keys.unwrap_or_default(),
+        drop_nulls: opt.drop_nulls,
+        drop_empty: opt.drop_empty,
+        drop_keys: opt.drop_keys.clone(),
+        drop_paths: opt.drop_paths.clone(),
+        limits: Limits {
+            max_depth: opt.max_depth,
+            max_string_bytes: opt.max_string_bytes,
+            max_number_length: opt.max_number_length,
+            max_tokens: opt.max_tokens,
+            max_input_bytes: opt.max_input_bytes,
+        },
+    };
+    if opt.pretty {
+        let style = Style {
+            indent: if opt.tabs {
+                Indent::Tabs
+            } else {
+                Indent::Spaces(opt.indent)
+            },
+            max_width: opt.width,
+            ..Style::default()
+        };
+        let bytes = fs::read(file)?;
+        let contents = json::decode(&bytes)?;
+        let prettified = Prettify::with_options(&contents, options, style)?;
+        for duplicate in prettified.duplicates() {
+            reporter.report(&duplicate.report());
+        }
+        println!("{}", prettified);
+        return Ok(0);
+    }
+
+    if ndjson {
+        let mut reader = BufReader::new(File::open(file)?);
+        skip_bom(&mut reader)?;
+        let (_, errors) = ndjson::Minifier::new(reader, stdout.lock())
+            .options(options)
+            .minify()?;
+        for error in &errors {
+            reporter.report(&error.report());
+        }
+        return Ok(errors.len());
+    }
+
+    if opt.recover {
+        let bytes = fs::read(file)?;
+        let contents = json::decode(&bytes)?;
+        let minified = Minify::recover(&contents, options)?;
+        for report in minified
+            .duplicates()
+            .iter()
+            .map(|duplicate| duplicate.report())
+        {
+            reporter.report(&report);
+        }
+        for diagnostic in minified.diagnostics() {
+            reporter.report(&diagnostic.report());
+        }
+        let mut stdout = stdout.lock();
+        minified.write_to(&mut stdout)?;
+        writeln!(stdout)?;
+        return Ok(0);
+    }
+
+    // Only UTF-8 can be minified as it is read, other encodings are
+    // converted as a whole first
+    let mut reader = BufReader::new(File::open(file)?);
+    if Encoding::detect(reader.fill_buf()?) != Encoding::Utf8 {
+        let mut contents = Vec::new();
+        reader.read_to_end(&mut contents)?;
+        let minified = Minify::from_bytes(&contents, options)?;
+        for duplicate in minified.duplicates() {
+            reporter.report(&duplicate.report());
+        }
+        let mut stdout = stdout.lock();
+        minified.write_to(&mut stdout)?;
+        writeln!(stdout)?;
+        return Ok(0);
+    }
+    skip_bom(&mut reader)?;
+
+    // Duplicates are reported once the file is done, as reporting them
+    // needs the whole file
+    let duplicates = Rc::new(RefCell::new(Vec::new()));
+    let found = Rc::clone(&duplicates);
+    let result = Minifier::new(reader, stdout.lock())
+        .options(options)
+        .on_duplicate(move |duplicate| found.borrow_mut().push(duplicate))
+        .minify();
+    let written = result.and_then(|mut stdout| writeln!(stdout));
+    for duplicate in duplicates.borrow().iter() {
+        reporter.report(&duplicate.report());
+    }
+    written?;
+    Ok(0)
+}
+
+/// Reports what the CSS lexer cannot read. Nothing is written, as CSS is
+/// not minified yet.
+#[cfg(feature = "css")]
+fn check_css(file: &Path, reporter: &mut Reporter) -> Result<usize, Box<dyn Error>> {
+    let bytes = fs::read(file)?;
+    let contents = json::decode(&bytes)?;
+    let reports = css::errors(&contents);
+    for report in &reports {
+        reporter.report(report);
+    }
+    if !reports.is_empty() {
+        return Err(format!("invalid CSS: {} errors", reports.len()).into());
+    }
+    Ok(0)
+}
+
+/// Skips a UTF-8 byte order mark, so positions are the same as in the
+/// input decoded with `json::decode`.
+fn skip_bom(reader: &mut impl BufRead) -> io::Result<()> {
+    if reader.fill_buf()?.starts_with(b"\xef\xbb\xbf") {
+        reader.consume(3);
+    }
     Ok(())
 }
+
+/// The file as text that reported spans are byte ranges of: the input
+/// converted to UTF-8 without a byte order mark. Invalid bytes in UTF-8
+/// become a `?` each so the rest keeps its offsets, as NDJSON reports errors
+/// after them. Other encodings fail at the first invalid sequence, which is
+/// all that needs to be shown.
+fn source_text(bytes: &[u8]) -> String {
+    if Encoding::detect(bytes) != Encoding::Utf8 {
+        return json::decode_lossy(bytes).into_owned();
+    }
+    let bytes = bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(bytes);
+    let mut text = String::with_capacity(bytes.len());
+    for chunk in bytes.utf8_chunks() {
+        text.push_str(chunk.valid());
+        text.extend(chunk.invalid().iter().map(|_| '?'));
+    }
+    text
+}
+
+/// Renders reports about a file to stderr, reading the file the first time
+/// one needs it.
+struct Reporter<'a> {
+    file: &'a Path,
+    renderer: Renderer,
+    source: Option<String>,
+}
+
+impl<'a> Reporter<'a> {
+    fn new(file: &'a Path, renderer: Renderer) -> Self {
+        Reporter {
+            file,
+            renderer,
+            source: None,
+        }
+    }
+
+    fn report(&mut self, report: &Report) {
+        let file = self.file;
+        let source = self
+            .source
+            .get_or_insert_with(|| source_text(&fs::read(file).unwrap_or_default()));
+        let name = file.display().to_string();
+        eprintln!("{}", self.renderer.render(&name, source, report));
+    }
+
+    /// Reports an error that stopped processing the file, with the input
+    /// around it if it is a JSON error.
+    fn error(&mut self, error: &(dyn Error + 'static)) {
+        let json = error.downcast_ref::<json::Error>().or_else(|| {
+            let error = error.downcast_ref::<io::Error>()?.get_ref()?;
+            error.downcast_ref::<json::Error>()
+        });
+        match json {
+            Some(error) => self.report(&error.report()),
+            None => {
+                let message = format!("{}: {}", self.file.display(), error);
+                eprint!("{}", self.renderer.render("", "", &Report::error(message)));
+            }
+        }
+    }
+}
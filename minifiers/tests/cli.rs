@@ -0,0 +1,112 @@
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Runs the binary on a file with `contents`, named `name` in a temporary
+/// directory.
+fn run(name: &str, contents: &[u8], args: &[&str]) -> Output {
+    let dir = env::temp_dir().join(format!("minifiers-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    let file: PathBuf = dir.join(name);
+    fs::write(&file, contents).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_minifiers"))
+        .args(args)
+        .arg(&file)
+        .env("NO_COLOR", "1")
+        .output()
+        .unwrap();
+    fs::remove_file(&file).unwrap();
+    output
+}
+
+fn utf16le(text: &str) -> Vec<u8> {
+    text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+}
+
+#[test]
+fn utf16() {
+    let output = run("utf16.json", &utf16le("\u{feff}{\"é\": [1, 2]}"), &[]);
+    assert!(output.status.success());
+    assert_eq!(output.stdout, "{\"é\":[1,2]}\n".as_bytes());
+}
+
+#[test]
+fn invalid_utf16() {
+    // An unpaired surrogate after a character that is two bytes in UTF-8
+    let contents = [&utf16le("[\"éa")[..], &[0x00, 0xd8], &utf16le("\"]")].concat();
+    for args in [&[][..], &["--pretty"], &["--recover"]] {
+        let output = run("invalid_utf16.json", &contents, args);
+        assert_eq!(output.status.code(), Some(1), "{:?}", args);
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(
+            stderr.starts_with("error: invalid UTF-16LE\n"),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("invalid_utf16.json:1:5\n"), "{}", stderr);
+        assert!(
+            stderr.contains("1 | [\"éa\u{fffd}\"]\n  |     ^\n"),
+            "{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn invalid_utf8() {
+    let output = run("invalid_utf8.json", b"[1, \"\xff\"]", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("invalid_utf8.json:1:6\n"), "{}", stderr);
+}
+
+#[test]
+fn ndjson_after_invalid_utf8() {
+    let input = b"\xef\xbb\xbf{\"a\":1}\n[\"\xff\xfe\"]\n{\"b\":2}\n[tru]\n";
+    let output = run("records.ndjson", input, &[]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(output.stdout, b"{\"a\":1}\n{\"b\":2}\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("records.ndjson:2:3\n"), "{}", stderr);
+    assert!(stderr.contains("2 | [\"??\"]\n  |   ^\n"), "{}", stderr);
+    // Later records are still pointed at correctly
+    assert!(
+        stderr.contains("error: unexpected character ']'\n --> "),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("4 | [tru]\n  |     ^\n"), "{}", stderr);
+}
+
+#[test]
+fn bom_positions() {
+    let output = run("bom.json", b"\xef\xbb\xbf[1,\n tru]", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("2 |  tru]\n  |     ^\n"), "{}", stderr);
+}
+
+#[cfg(feature = "css")]
+#[test]
+fn css() {
+    let output = run("valid.css", b"a { color: red; }", &[]);
+    assert!(output.status.success());
+    assert!(output.stdout.is_empty());
+
+    let output = run("invalid.css", b"a {}\n  /* open", &[]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("error: unterminated comment\n"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("invalid.css:2:3\n"), "{}", stderr);
+    assert!(
+        stderr.contains("help: close the comment with `*/`"),
+        "{}",
+        stderr
+    );
+    assert!(stderr.contains("invalid CSS: 1 errors"), "{}", stderr);
+}